@@ -1,4 +1,4 @@
-use super::parse::CallDef;
+use super::parse::{CallDef, CallerArg};
 use quote::quote;
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
@@ -8,6 +8,17 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 	// This is a vector of all the callable function names.
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
 
+	// This is the second argument passed to each of the functions in `fn_name`. Functions taking a
+	// `caller: T::AccountId` can only be called by a signed origin, while functions taking an
+	// `origin: T::RuntimeOrigin` check the origin themselves.
+	let caller_arg = methods
+		.iter()
+		.map(|method| match method.caller {
+			CallerArg::Signed => quote! { crate::system::ensure_signed::<T>(origin)? },
+			CallerArg::Origin => quote! { origin },
+		})
+		.collect::<Vec<_>>();
+
 	// This is a nested vector of all the arguments for each of the functions in `fn_name`. It does
 	// not include the `self` or `caller: T::AccountId` parameter, which we always assume are the
 	// first two parameters to these calls.
@@ -23,42 +34,178 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// This is the weight expression of each call.
+	let weight = methods.iter().map(|method| &method.weight).collect::<Vec<_>>();
+
+	// This is the index of each call, which is used to identify the call when it is encoded.
+	let call_index = methods.iter().map(|method| method.index).collect::<Vec<_>>();
+
+	// The stringified names of each function and of its arguments, the stringified types of its
+	// arguments, and its docs, for the metadata.
+	let fn_str = fn_name.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+	let args_str = args_name
+		.iter()
+		.map(|names| names.iter().map(|name| name.to_string()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let args_type_str = args_type
+		.iter()
+		.map(|types| types.iter().map(|type_| crate::type_name::type_name(type_)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let docs = methods.iter().map(|method| &method.docs).collect::<Vec<_>>();
+
+	// This is a list of all the argument types, which must be encodable for the `Call` to be.
+	let all_args_type = args_type.iter().flatten().collect::<Vec<_>>();
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
-	// and the `Dispatch` trait logic to route a `caller` to access those functions.
+	// and the `Dispatch` trait logic to route an `origin` to access those functions.
 	let dispatch_impl = quote! {
 		// The callable functions exposed by this pallet.
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
+		//
+		// The hidden variant can never be constructed. It uses `T`, even when the pallet has no
+		// callable functions at all.
 		#[allow(non_camel_case_types)]
 		pub enum Call<T: Config> {
 			#(
 				#fn_name { #( #args_name: #args_type),* },
 			)*
+			#[doc(hidden)]
+			#[allow(dead_code)]
+			__Ignore(core::marker::PhantomData<T>, core::convert::Infallible),
 		}
 
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
-		// appropriate function call with all arguments, including the `caller`.
+		// appropriate function call with all arguments, including the `caller` or `origin`.
 		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
-			type Caller = T::AccountId;
+			type Origin = T::RuntimeOrigin;
 			type Call = Call<T>;
 
-			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
+			fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> crate::support::DispatchResult {
 				match call {
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
 							self.#fn_name(
-								// Note that we assume the first argument of every call is the `caller`.
-								caller,
+								// Note that we assume the first argument of every call is the `caller`
+								// or the `origin`.
+								#caller_arg,
 								#( #args_name ),*
-							)?;
+							)
 						},
 					)*
+					Call::__Ignore(_, never) => match never {},
+				}
+			}
+		}
+	};
+
+	// This quote block implements `Clone` for the `enum Call`. We can't derive it, since that would
+	// require `T: Clone`, while only the arguments need to be cloned.
+	let clone_impl = quote! {
+		impl<T: Config> Clone for Call<T>
+		where
+			#( #all_args_type: Clone, )*
+		{
+			fn clone(&self) -> Self {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => Call::#fn_name { #( #args_name: #args_name.clone() ),* },
+					)*
+					Call::__Ignore(_, never) => match *never {},
 				}
-				Ok(())
+			}
+		}
+	};
+
+	// This quote block implements `Encode` for the `enum Call`, so that calls can be signed. Each
+	// call is encoded as its index followed by its arguments.
+	let encode_impl = quote! {
+		impl<T: Config> crate::support::Encode for Call<T>
+		where
+			#( #all_args_type: crate::support::Encode, )*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							dest.push(#call_index);
+							#( crate::support::Encode::encode_to(#args_name, dest); )*
+						},
+					)*
+					Call::__Ignore(_, never) => match *never {},
+				}
+			}
+		}
+	};
+
+	// This quote block implements `Decode` for the `enum Call`, reading the encoding of `Encode`.
+	let decode_impl = quote! {
+		impl<T: Config> crate::support::Decode for Call<T>
+		where
+			#( #all_args_type: crate::support::Decode, )*
+		{
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				match <u8 as crate::support::Decode>::decode(input)? {
+					#(
+						#call_index => Ok(Call::#fn_name { #( #args_name: crate::support::Decode::decode(input)? ),* }),
+					)*
+					_ => Err(crate::support::codec::Error("unknown call index")),
+				}
+			}
+		}
+	};
+
+	// This quote block implements `GetDispatchInfo` for the `enum Call`. The arguments of the call
+	// are in scope when evaluating its weight expression.
+	let dispatch_info_impl = quote! {
+		impl<T: Config> crate::support::GetDispatchInfo for Call<T> {
+			#[allow(unused_variables)]
+			fn get_dispatch_info(&self) -> crate::support::DispatchInfo {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							crate::support::DispatchInfo { weight: #weight }
+						},
+					)*
+					Call::__Ignore(_, never) => match *never {},
+				}
+			}
+		}
+	};
+
+	// This quote block describes every call for the runtime metadata.
+	let metadata_impl = quote! {
+		impl<T: Config> Call<T> {
+			pub fn metadata() -> Vec<crate::support::metadata::CallMetadata> {
+				vec![
+					#(
+						crate::support::metadata::CallMetadata {
+							name: #fn_str.to_string(),
+							index: #call_index,
+							args: vec![
+								#(
+									crate::support::metadata::FieldMetadata {
+										name: #args_str.to_string(),
+										ty: #args_type_str.to_string(),
+									},
+								)*
+							],
+							docs: vec![ #( #docs.to_string() ),* ],
+						},
+					)*
+				]
 			}
 		}
 	};
 
 	// Return the generated code.
-	dispatch_impl.into()
+	quote! {
+		#dispatch_impl
+		#clone_impl
+		#encode_impl
+		#decode_impl
+		#dispatch_info_impl
+		#metadata_impl
+	}
+	.into()
 }
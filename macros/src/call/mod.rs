@@ -1,3 +1,5 @@
+use quote::ToTokens;
+
 pub mod expand;
 pub mod parse;
 
@@ -6,11 +8,7 @@ pub fn call(
 	_attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-	// The final expanded code will be placed here.
-	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
 
 	// First we parse the call functions implemented for the pallet...
 	let generated: proc_macro::TokenStream = match parse::CallDef::try_from(item_mod.clone()) {
@@ -19,7 +17,26 @@ pub fn call(
 		Err(e) => e.to_compile_error().into(),
 	};
 
+	// Our final product contains all of our old code too, except for the `#[weight(..)]` and
+	// `#[call_index(..)]` attributes, which are only meaningful to this macro.
+	remove_call_attrs(&mut item_mod);
+	let mut finished: proc_macro::TokenStream = item_mod.to_token_stream().into();
+
 	// Add our generated code to the end, and return the final result.
 	finished.extend(generated);
 	return finished;
 }
+
+/// Remove the `#[weight(..)]` and `#[call_index(..)]` attributes from all the functions of the
+/// `impl` block.
+fn remove_call_attrs(item: &mut syn::Item) {
+	if let syn::Item::Impl(item_impl) = item {
+		for item in item_impl.items.iter_mut() {
+			if let syn::ImplItem::Fn(method) = item {
+				method.attrs.retain(|attr| {
+					!attr.path().is_ident(parse::WEIGHT_ATTR) && !attr.path().is_ident(parse::CALL_INDEX_ATTR)
+				});
+			}
+		}
+	}
+}
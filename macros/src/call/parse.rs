@@ -5,8 +5,15 @@ use syn::spanned::Spanned;
 mod keyword {
 	syn::custom_keyword!(T);
 	syn::custom_keyword!(AccountId);
+	syn::custom_keyword!(RuntimeOrigin);
 }
 
+/// The name of the attribute declaring the weight of a call, e.g. `#[weight(10_000)]`.
+pub const WEIGHT_ATTR: &str = "weight";
+
+/// The name of the attribute declaring the index of a call, e.g. `#[call_index(0)]`.
+pub const CALL_INDEX_ATTR: &str = "call_index";
+
 /// This object will collect all the information we need to keep while parsing the callable
 /// functions.
 #[derive(Debug)]
@@ -18,13 +25,31 @@ pub struct CallDef {
 	pub methods: Vec<CallVariantDef>,
 }
 
+/// The second argument of a callable function, which says who may call it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallerArg {
+	/// `caller: T::AccountId`, for calls which can only be made by a signed origin.
+	Signed,
+	/// `origin: T::RuntimeOrigin`, for calls which check the origin themselves.
+	Origin,
+}
+
 /// This is the metadata we keep about each callable function in our pallet.
 #[derive(Debug)]
 pub struct CallVariantDef {
 	/// The function name.
 	pub name: syn::Ident,
+	/// The kind of the second argument of the function.
+	pub caller: CallerArg,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The weight expression from the `#[weight(..)]` attribute of the function.
+	pub weight: syn::Expr,
+	/// The index identifying the call when it is encoded, from the `#[call_index(..)]` attribute of
+	/// the function.
+	pub index: u8,
+	/// The doc comments of the function.
+	pub docs: Vec<String>,
 }
 
 impl CallDef {
@@ -44,7 +69,7 @@ impl CallDef {
 		};
 
 		// Here is where we will store all the callable functions.
-		let mut methods = vec![];
+		let mut methods: Vec<CallVariantDef> = vec![];
 		for item in item_impl.items {
 			if let syn::ImplItem::Fn(method) = item {
 				// Here is where we will store all the args for each callable functions.
@@ -59,22 +84,46 @@ impl CallDef {
 					},
 				}
 
-				// The second argument should be the `caller: T::AccountId` argument.
-				match method.sig.inputs.iter().skip(1).next() {
+				// The second argument should be the `caller: T::AccountId` or the
+				// `origin: T::RuntimeOrigin` argument.
+				let caller = match method.sig.inputs.iter().skip(1).next() {
 					Some(syn::FnArg::Typed(arg)) => {
-						// Here we specifically check that this argument is as we expect for
-						// `caller: T::AccountId`.
-						check_caller_arg(arg)?;
+						// Here we specifically check that this argument is as we expect.
+						check_caller_arg(arg)?
 					},
 					_ => {
-						let msg = "Invalid call, second argument should be `caller: T::AccountId`";
+						let msg = "Invalid call, second argument should be `caller: T::AccountId` or \
+							`origin: T::RuntimeOrigin`";
 						return Err(syn::Error::new(method.sig.span(), msg))
 					},
-				}
+				};
 
 				let fn_name = method.sig.ident.clone();
 
-				// Parsing the rest of the args. Skipping 2 for `self` and `caller`.
+				// Every call must declare its weight with a `#[weight(..)]` attribute.
+				let weight = match method.attrs.iter().find(|attr| attr.path().is_ident(WEIGHT_ATTR)) {
+					Some(attr) => attr.parse_args::<syn::Expr>()?,
+					None => {
+						let msg = "Invalid call, expected a `#[weight(..)]` attribute";
+						return Err(syn::Error::new(method.sig.span(), msg))
+					},
+				};
+
+				// Every call must declare the index identifying it in its encoding, so that the encoding
+				// does not depend on the order of the calls.
+				let index = match method.attrs.iter().find(|attr| attr.path().is_ident(CALL_INDEX_ATTR)) {
+					Some(attr) => attr.parse_args::<syn::LitInt>()?.base10_parse::<u8>()?,
+					None => {
+						let msg = "Invalid call, expected a `#[call_index(..)]` attribute";
+						return Err(syn::Error::new(method.sig.span(), msg))
+					},
+				};
+				if methods.iter().any(|other| other.index == index) {
+					let msg = format!("Invalid pallet::call, index {} is used by more than one call", index);
+					return Err(syn::Error::new(method.sig.span(), msg))
+				}
+
+				// Parsing the rest of the args. Skipping 2 for `self` and `caller` or `origin`.
 				for arg in method.sig.inputs.iter().skip(2) {
 					// All arguments should be typed.
 					let arg = if let syn::FnArg::Typed(arg) = arg {
@@ -96,7 +145,8 @@ impl CallDef {
 				}
 
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				let docs = crate::docs::docs(&method.attrs);
+				methods.push(CallVariantDef { name: fn_name, caller, args, weight, index, docs });
 			}
 		}
 
@@ -105,37 +155,46 @@ impl CallDef {
 	}
 }
 
-/// Check caller arg is exactly: `caller: T::AccountId`.
+/// Check the caller arg is exactly `caller: T::AccountId` or `origin: T::RuntimeOrigin`.
 ///
 /// This is kept strict to keep the code simple.
-pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<()> {
-	pub struct CheckDispatchableFirstArg;
+pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<CallerArg> {
+	pub struct CheckDispatchableFirstArg(CallerArg);
 	impl syn::parse::Parse for CheckDispatchableFirstArg {
 		fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
 			input.parse::<keyword::T>()?;
 			input.parse::<syn::Token![::]>()?;
-			input.parse::<keyword::AccountId>()?;
-			Ok(Self)
-		}
-	}
-
-	// This checks the arg name is `caller` or `_caller`.
-	if let syn::Pat::Ident(ident) = &*arg.pat {
-		// We also support the name as `_caller` for when the variable is unused.
-		if &ident.ident != "caller" && &ident.ident != "_caller" {
-			let msg = "Invalid name for second parameter: expected `caller: T::AccountId`";
-			return Err(syn::Error::new(ident.span(), msg))
+			if input.peek(keyword::RuntimeOrigin) {
+				input.parse::<keyword::RuntimeOrigin>()?;
+				Ok(Self(CallerArg::Origin))
+			} else {
+				input.parse::<keyword::AccountId>()?;
+				Ok(Self(CallerArg::Signed))
+			}
 		}
 	}
 
-	// This checks the type is `T::AccountId` with `CheckDispatchableFirstArg`
+	// This checks the type is `T::AccountId` or `T::RuntimeOrigin` with `CheckDispatchableFirstArg`.
 	let ty = &arg.ty;
-	syn::parse2::<CheckDispatchableFirstArg>(ty.to_token_stream()).map_err(|e| {
-		let msg = "Invalid type for second parameter: expected `caller: T::AccountId`";
+	let CheckDispatchableFirstArg(caller) = syn::parse2(ty.to_token_stream()).map_err(|e| {
+		let msg = "Invalid type for second parameter: expected `caller: T::AccountId` or `origin: T::RuntimeOrigin`";
 		let mut err = syn::Error::new(ty.span(), msg);
 		err.combine(e);
 		err
 	})?;
 
-	Ok(())
+	// This checks the arg name is `caller` or `_caller` for `T::AccountId`, and `origin` or
+	// `_origin` for `T::RuntimeOrigin`. The names with a leading underscore are for when the
+	// variable is unused.
+	if let syn::Pat::Ident(ident) = &*arg.pat {
+		let (name, msg) = match caller {
+			CallerArg::Signed => ("caller", "Invalid name for second parameter: expected `caller: T::AccountId`"),
+			CallerArg::Origin => ("origin", "Invalid name for second parameter: expected `origin: T::RuntimeOrigin`"),
+		};
+		if ident.ident != name && ident.ident != format!("_{}", name) {
+			return Err(syn::Error::new(ident.span(), msg))
+		}
+	}
+
+	Ok(caller)
 }
@@ -0,0 +1,125 @@
+use super::parse::{CodecData, CodecDef};
+use quote::{format_ident, quote};
+
+/// The pattern binding every field of a struct or enum variant, and the names of the bindings.
+///
+/// Unnamed fields are bound to `field_0`, `field_1`, etc.
+fn fields_pattern(fields: &syn::Fields) -> (proc_macro2::TokenStream, Vec<syn::Ident>) {
+	match fields {
+		syn::Fields::Named(named) => {
+			let names = named.named.iter().map(|field| field.ident.clone().unwrap()).collect::<Vec<_>>();
+			(quote! { { #( #names ),* } }, names)
+		},
+		syn::Fields::Unnamed(unnamed) => {
+			let names = (0..unnamed.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect::<Vec<_>>();
+			(quote! { ( #( #names ),* ) }, names)
+		},
+		syn::Fields::Unit => (quote! {}, vec![]),
+	}
+}
+
+/// The expression decoding every field of a struct or enum variant from `input`, in order.
+fn decode_fields(fields: &syn::Fields) -> proc_macro2::TokenStream {
+	match fields {
+		syn::Fields::Named(named) => {
+			let names = named.named.iter().map(|field| field.ident.clone().unwrap());
+			quote! { { #( #names: crate::support::Decode::decode(input)? ),* } }
+		},
+		syn::Fields::Unnamed(unnamed) => {
+			let decode = unnamed.unnamed.iter().map(|_| quote! { crate::support::Decode::decode(input)? });
+			quote! { ( #( #decode ),* ) }
+		},
+		syn::Fields::Unit => quote! {},
+	}
+}
+
+/// The generics of the type, where every type parameter is bound by `bound`.
+fn bounded_generics(generics: &syn::Generics, bound: proc_macro2::TokenStream) -> syn::Generics {
+	let mut generics = generics.clone();
+	let params = generics.type_params().map(|param| param.ident.clone()).collect::<Vec<_>>();
+	let where_clause = generics.make_where_clause();
+	for param in params {
+		where_clause.predicates.push(syn::parse_quote! { #param: #bound });
+	}
+	generics
+}
+
+/// See the `fn derive_encode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_encode(def: CodecDef) -> proc_macro2::TokenStream {
+	let CodecDef { name, generics, data } = def;
+	let generics = bounded_generics(&generics, quote! { crate::support::Encode });
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// Every field is encoded in order. The fields of an enum variant come after its index.
+	let body = match data {
+		CodecData::Struct(fields) => {
+			let (pattern, names) = fields_pattern(&fields);
+			quote! {
+				let Self #pattern = self;
+				#( crate::support::Encode::encode_to(#names, dest); )*
+			}
+		},
+		CodecData::Enum(variants) => {
+			let arms = variants.iter().map(|variant| {
+				let (variant_name, index) = (&variant.name, variant.index);
+				let (pattern, names) = fields_pattern(&variant.fields);
+				quote! {
+					Self::#variant_name #pattern => {
+						dest.push(#index);
+						#( crate::support::Encode::encode_to(#names, dest); )*
+					},
+				}
+			});
+			quote! {
+				match self {
+					#( #arms )*
+				}
+			}
+		},
+	};
+
+	quote! {
+		impl #impl_generics crate::support::Encode for #name #ty_generics #where_clause {
+			#[allow(unused_variables)]
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				#body
+			}
+		}
+	}
+}
+
+/// See the `fn derive_decode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_decode(def: CodecDef) -> proc_macro2::TokenStream {
+	let CodecDef { name, generics, data } = def;
+	let generics = bounded_generics(&generics, quote! { crate::support::Decode });
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	let body = match data {
+		CodecData::Struct(fields) => {
+			let fields = decode_fields(&fields);
+			quote! { Ok(Self #fields) }
+		},
+		CodecData::Enum(variants) => {
+			let arms = variants.iter().map(|variant| {
+				let (variant_name, index) = (&variant.name, variant.index);
+				let fields = decode_fields(&variant.fields);
+				quote! { #index => Ok(Self::#variant_name #fields), }
+			});
+			quote! {
+				match <u8 as crate::support::Decode>::decode(input)? {
+					#( #arms )*
+					_ => Err(crate::support::codec::Error("unknown variant index")),
+				}
+			}
+		},
+	};
+
+	quote! {
+		impl #impl_generics crate::support::Decode for #name #ty_generics #where_clause {
+			#[allow(unused_variables)]
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				#body
+			}
+		}
+	}
+}
@@ -0,0 +1,20 @@
+pub mod expand;
+pub mod parse;
+
+/// See the `fn derive_encode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn derive_encode(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let input = syn::parse_macro_input!(item as syn::DeriveInput);
+	match parse::CodecDef::try_from(input) {
+		Ok(def) => expand::expand_encode(def).into(),
+		Err(e) => e.to_compile_error().into(),
+	}
+}
+
+/// See the `fn derive_decode` docs at the `lib.rs` of this crate for a high level definition.
+pub fn derive_decode(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let input = syn::parse_macro_input!(item as syn::DeriveInput);
+	match parse::CodecDef::try_from(input) {
+		Ok(def) => expand::expand_decode(def).into(),
+		Err(e) => e.to_compile_error().into(),
+	}
+}
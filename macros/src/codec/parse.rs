@@ -0,0 +1,73 @@
+use syn::spanned::Spanned;
+
+// Custom keywords we match to when parsing the `#[codec(..)]` attribute.
+mod keyword {
+	syn::custom_keyword!(index);
+}
+
+/// The name of the attribute giving the index of an enum variant, e.g. `#[codec(index = 1)]`.
+pub const CODEC_ATTR: &str = "codec";
+
+/// This object will collect all the information we need to keep while parsing a type deriving
+/// `Encode` or `Decode`.
+#[derive(Debug)]
+pub struct CodecDef {
+	/// The name of the type.
+	pub name: syn::Ident,
+	/// The generics of the type. Every type parameter must implement the derived trait.
+	pub generics: syn::Generics,
+	pub data: CodecData,
+}
+
+#[derive(Debug)]
+pub enum CodecData {
+	/// The fields of a struct, which are encoded in declaration order.
+	Struct(syn::Fields),
+	/// The variants of an enum. Each variant is encoded as its index, followed by its fields.
+	Enum(Vec<VariantDef>),
+}
+
+#[derive(Debug)]
+pub struct VariantDef {
+	pub name: syn::Ident,
+	pub fields: syn::Fields,
+	/// The index from the `#[codec(index = ..)]` attribute of the variant, or else its position in
+	/// the enum.
+	pub index: u8,
+}
+
+impl CodecDef {
+	pub fn try_from(input: syn::DeriveInput) -> syn::Result<Self> {
+		let data = match input.data {
+			syn::Data::Struct(data) => CodecData::Struct(data.fields),
+			syn::Data::Enum(data) => {
+				let mut variants: Vec<VariantDef> = vec![];
+				for (position, variant) in data.variants.into_iter().enumerate() {
+					let index = match variant.attrs.iter().find(|attr| attr.path().is_ident(CODEC_ATTR)) {
+						Some(attr) => attr.parse_args_with(parse_index)?,
+						None => u8::try_from(position)
+							.map_err(|_| syn::Error::new(variant.span(), "Invalid codec, too many variants"))?,
+					};
+					if variants.iter().any(|other| other.index == index) {
+						let msg = format!("Invalid codec, index {} is used by more than one variant", index);
+						return Err(syn::Error::new(variant.span(), msg))
+					}
+					variants.push(VariantDef { name: variant.ident, fields: variant.fields, index });
+				}
+				CodecData::Enum(variants)
+			},
+			syn::Data::Union(data) => {
+				return Err(syn::Error::new(data.union_token.span(), "Invalid codec, unions are not supported"))
+			},
+		};
+
+		Ok(Self { name: input.ident, generics: input.generics, data })
+	}
+}
+
+/// Parse the contents of `#[codec(index = ..)]`.
+fn parse_index(input: syn::parse::ParseStream) -> syn::Result<u8> {
+	input.parse::<keyword::index>()?;
+	input.parse::<syn::Token![=]>()?;
+	input.parse::<syn::LitInt>()?.base10_parse::<u8>()
+}
@@ -0,0 +1,18 @@
+/// The lines of the doc comments in `attrs`, which the macros include in the runtime metadata.
+pub fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+	attrs
+		.iter()
+		.filter_map(|attr| match &attr.meta {
+			syn::Meta::NameValue(syn::MetaNameValue {
+				path,
+				value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+				..
+			}) if path.is_ident("doc") => {
+				// `/// docs` is `#[doc = " docs"]`, we drop the leading space.
+				let doc = doc.value();
+				Some(doc.strip_prefix(' ').unwrap_or(&doc).to_string())
+			},
+			_ => None,
+		})
+		.collect()
+}
@@ -0,0 +1,65 @@
+use super::parse::ErrorDef;
+use quote::quote;
+
+/// See the `fn error` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_error(def: ErrorDef) -> proc_macro2::TokenStream {
+	let ErrorDef { error_enum, variants, docs } = def;
+
+	// The index of each error, which identifies it within the pallet.
+	let error_index = (0..variants.len()).map(|i| i as u8).collect::<Vec<_>>();
+
+	// The stringified error names, used to describe errors.
+	let error_name = variants.iter().map(|variant| variant.to_string()).collect::<Vec<_>>();
+
+	// This quote block names and indexes each error, and converts errors to a `DispatchError`.
+	quote! {
+		impl #error_enum {
+			// The index of this error within the pallet.
+			pub fn index(&self) -> u8 {
+				match *self {
+					#( Self::#variants => #error_index, )*
+				}
+			}
+
+			// The name of this error.
+			pub fn name(&self) -> &'static str {
+				match *self {
+					#( Self::#variants => #error_name, )*
+				}
+			}
+
+			// The name of the error at `index`, e.g. to describe a decoded `ModuleError`.
+			pub fn name_of(index: u8) -> Option<&'static str> {
+				match index {
+					#( #error_index => Some(#error_name), )*
+					_ => None,
+				}
+			}
+
+			// Describe every error for the runtime metadata.
+			pub fn metadata() -> Vec<crate::support::metadata::ErrorMetadata> {
+				vec![
+					#(
+						crate::support::metadata::ErrorMetadata {
+							name: #error_name.to_string(),
+							index: #error_index,
+							docs: vec![ #( #docs.to_string() ),* ],
+						},
+					)*
+				]
+			}
+		}
+
+		// The pallet does not know its own index in the runtime, so this is filled in by the
+		// runtime when the error is first returned from a dispatch, see `DispatchError::in_pallet`.
+		impl From<#error_enum> for crate::support::DispatchError {
+			fn from(error: #error_enum) -> Self {
+				crate::support::DispatchError::Module(crate::support::ModuleError {
+					pallet_index: None,
+					error_index: error.index(),
+					message: Some(error.name()),
+				})
+			}
+		}
+	}
+}
@@ -0,0 +1,47 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Error` enum.
+#[derive(Debug)]
+pub struct ErrorDef {
+	/// This is the name of the enum used by the user. We mostly assume it is `Error`.
+	pub error_enum: syn::Ident,
+	/// This is the list of the error variant names, in declaration order.
+	pub variants: Vec<syn::Ident>,
+	/// The doc comments of each error in `variants`.
+	pub docs: Vec<Vec<String>>,
+}
+
+impl ErrorDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::error, expected item enum"))
+		};
+
+		// Errors are identified by their index only, so they can't be generic or carry any data.
+		if !item_enum.generics.params.is_empty() {
+			let msg = "Invalid pallet::error, the error enum can't be generic";
+			return Err(syn::Error::new(item_enum.generics.span(), msg))
+		}
+
+		let mut variants = vec![];
+		let mut docs = vec![];
+		for variant in item_enum.variants {
+			if !matches!(variant.fields, syn::Fields::Unit) {
+				let msg = "Invalid pallet::error, errors can't have fields";
+				return Err(syn::Error::new(variant.fields.span(), msg))
+			}
+			docs.push(crate::docs::docs(&variant.attrs));
+			variants.push(variant.ident);
+		}
+
+		if variants.len() > u8::MAX as usize {
+			let msg = "Invalid pallet::error, too many errors";
+			return Err(syn::Error::new(item_enum.ident.span(), msg))
+		}
+
+		Ok(Self { error_enum: item_enum.ident, variants, docs })
+	}
+}
@@ -0,0 +1,169 @@
+use super::parse::EventDef;
+use quote::{format_ident, quote};
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_event(def: EventDef) -> proc_macro2::TokenStream {
+	let EventDef { event_enum, generics, variants } = def;
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+	// The existing where clause predicates, which we extend with bounds on the field types.
+	let predicates = where_clause.map(|clause| clause.predicates.iter().collect::<Vec<_>>()).unwrap_or_default();
+
+	// This is a vector of all the event names.
+	let variant_name = variants.iter().map(|variant| &variant.name).collect::<Vec<_>>();
+
+	// This is a vector of the stringified event names, used when formatting events.
+	let variant_str = variants.iter().map(|variant| variant.name.to_string()).collect::<Vec<_>>();
+
+	// This is a nested vector of all the field names for each of the events in `variant_name`.
+	let field_name = variants
+		.iter()
+		.map(|variant| variant.fields.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// The same as `field_name`, stringified.
+	let field_str = field_name
+		.iter()
+		.map(|names| names.iter().map(|name| name.to_string()).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// The same as `field_name`, but renamed so that we can bind the fields of two events at once.
+	let other_field_name = field_name
+		.iter()
+		.map(|names| names.iter().map(|name| format_ident!("other_{}", name)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+
+	// The stringified types of the fields of each event, and the docs of each event, for the
+	// metadata.
+	let variant_field_type_str = variants
+		.iter()
+		.map(|variant| {
+			variant
+				.fields
+				.iter()
+				.map(|(_, type_)| crate::type_name::type_name(type_))
+				.collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+	let docs = variants.iter().map(|variant| &variant.docs).collect::<Vec<_>>();
+
+	// The index of each event, which identifies it in the encoding.
+	let variant_index = (0..variants.len()).map(|i| i as u8).collect::<Vec<_>>();
+
+	// This is a list of all the field types, which must implement each trait for the event to
+	// implement it.
+	let field_type =
+		variants.iter().flat_map(|variant| variant.fields.iter().map(|(_, type_)| type_)).collect::<Vec<_>>();
+
+	// This quote block implements `Clone`, `PartialEq`, `Debug` and the codec for the event enum. We
+	// can't derive them, since that would require `T` to implement them, while only the fields need
+	// to.
+	quote! {
+		impl #impl_generics Clone for #event_enum #type_generics
+		where
+			#( #predicates, )*
+			#( #field_type: Clone, )*
+		{
+			fn clone(&self) -> Self {
+				match self {
+					#(
+						Self::#variant_name { #( #field_name ),* } => {
+							Self::#variant_name { #( #field_name: #field_name.clone() ),* }
+						},
+					)*
+				}
+			}
+		}
+
+		impl #impl_generics PartialEq for #event_enum #type_generics
+		where
+			#( #predicates, )*
+			#( #field_type: PartialEq, )*
+		{
+			fn eq(&self, other: &Self) -> bool {
+				#[allow(unreachable_patterns)]
+				match (self, other) {
+					#(
+						(
+							Self::#variant_name { #( #field_name ),* },
+							Self::#variant_name { #( #field_name: #other_field_name ),* },
+						) => true #( && #field_name == #other_field_name )*,
+					)*
+					_ => false,
+				}
+			}
+		}
+
+		impl #impl_generics core::fmt::Debug for #event_enum #type_generics
+		where
+			#( #predicates, )*
+			#( #field_type: core::fmt::Debug, )*
+		{
+			fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+				match self {
+					#(
+						Self::#variant_name { #( #field_name ),* } => {
+							f.debug_struct(#variant_str) #( .field(#field_str, #field_name) )* .finish()
+						},
+					)*
+				}
+			}
+		}
+
+		impl #impl_generics crate::support::Encode for #event_enum #type_generics
+		where
+			#( #predicates, )*
+			#( #field_type: crate::support::Encode, )*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Self::#variant_name { #( #field_name ),* } => {
+							dest.push(#variant_index);
+							#( crate::support::Encode::encode_to(#field_name, dest); )*
+						},
+					)*
+				}
+			}
+		}
+
+		impl #impl_generics crate::support::Decode for #event_enum #type_generics
+		where
+			#( #predicates, )*
+			#( #field_type: crate::support::Decode, )*
+		{
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				match <u8 as crate::support::Decode>::decode(input)? {
+					#(
+						#variant_index => Ok(Self::#variant_name {
+							#( #field_name: crate::support::Decode::decode(input)? ),*
+						}),
+					)*
+					_ => Err(crate::support::codec::Error("invalid event")),
+				}
+			}
+		}
+
+		// Describe every event for the runtime metadata.
+		impl #impl_generics #event_enum #type_generics #where_clause {
+			pub fn metadata() -> Vec<crate::support::metadata::EventMetadata> {
+				vec![
+					#(
+						crate::support::metadata::EventMetadata {
+							name: #variant_str.to_string(),
+							fields: vec![
+								#(
+									crate::support::metadata::FieldMetadata {
+										name: #field_str.to_string(),
+										ty: #variant_field_type_str.to_string(),
+									},
+								)*
+							],
+							docs: vec![ #( #docs.to_string() ),* ],
+						},
+					)*
+				]
+			}
+		}
+	}
+}
@@ -0,0 +1,56 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Event` enum.
+#[derive(Debug)]
+pub struct EventDef {
+	/// This is the name of the enum used by the user. We mostly assume it is `Event`.
+	pub event_enum: syn::Ident,
+	/// The generics of the enum, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is the list of events in the enum. See `EventVariantDef`.
+	pub variants: Vec<EventVariantDef>,
+}
+
+/// This is the metadata we keep about each event.
+#[derive(Debug)]
+pub struct EventVariantDef {
+	/// The variant name.
+	pub name: syn::Ident,
+	/// Information on the fields of the event: `(name, type)`.
+	pub fields: Vec<(syn::Ident, syn::Type)>,
+	/// The doc comments of the event.
+	pub docs: Vec<String>,
+}
+
+impl EventDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::event, expected item enum"))
+		};
+
+		let mut variants = vec![];
+		for variant in item_enum.variants {
+			// We only support events with named fields, or no fields at all. Named fields make it
+			// clear what each value of the event means.
+			let fields = match variant.fields {
+				syn::Fields::Named(fields) => fields
+					.named
+					.into_iter()
+					.map(|field| (field.ident.expect("named fields have an ident"), field.ty))
+					.collect(),
+				syn::Fields::Unit => vec![],
+				syn::Fields::Unnamed(fields) => {
+					let msg = "Invalid pallet::event, event fields must be named";
+					return Err(syn::Error::new(fields.span(), msg))
+				},
+			};
+			let docs = crate::docs::docs(&variant.attrs);
+			variants.push(EventVariantDef { name: variant.ident, fields, docs });
+		}
+
+		Ok(Self { event_enum: item_enum.ident, generics: item_enum.generics, variants })
+	}
+}
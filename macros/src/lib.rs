@@ -1,6 +1,31 @@
 mod call;
+mod codec;
+mod docs;
+mod error;
+mod event;
 mod runtime;
+mod type_name;
 
+/// Expand the callable functions of a pallet.
+///
+/// This generates code needed for dispatching calls to the pallet:
+/// - `enum Call` - an enum with one variant for each callable function, containing its arguments.
+/// - implements the trait `support::Dispatch` to route each `Call` to the matching function.
+/// - The second argument of every callable function is either `caller: T::AccountId`, for calls
+///   which can only be made by a signed origin, or `origin: T::RuntimeOrigin`, for calls which
+///   check the origin themselves, e.g. with `system::ensure_root`.
+/// - implements `Clone` and the traits `support::Encode` and `support::Decode` for `Call`, where
+///   each call is identified by the index from its `#[call_index(n)]` attribute, which every
+///   callable function must have. Explicit indices keep the encoding of calls stable when calls
+///   are added, removed or reordered.
+/// - implements the trait `support::GetDispatchInfo` for `Call`. Every callable function must have
+///   a `#[weight(expr)]` attribute, where `expr` evaluates to a `support::Weight` and can use the
+///   arguments of the function.
+/// - `fn metadata()` on `Call`, which describes each call: its name, index, arguments and docs. The
+///   types of the arguments are described as they are written, e.g. `T::AccountId`.
+///
+/// `enum Call` also has a hidden variant which can't be constructed, so that pallets without any
+/// callable functions can use this macro on an empty `impl` block.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -13,18 +38,89 @@ pub fn call(
 ///
 /// This generates function implementations on `Runtime`:
 /// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
+///   included in the runtime. `fn with_storage()` does the same on top of the given
+///   `support::Storage`. Every pallet has a `fn new()` taking the storage shared by all pallets,
+///   which it uses for its storage items, see `support::storage`.
+/// - `fn apply_extrinsic()` - which applies a single extrinsic. Extrinsics with an invalid
+///   signature, whose nonce does not match the caller's nonce in the system pallet, or whose weight
+///   does not fit in the block, are rejected. Otherwise the nonce is incremented and the call is
+///   dispatched inside its own storage transaction, so a failing call has all of its writes rolled
+///   back.
+/// - If the attribute names the pallet charging fees and the pallet holding the balances they are
+///   paid from, e.g. `#[macros::runtime(transaction_payment = transaction_payment, currency =
+///   balances)]`, `fn apply_extrinsic()` also withdraws the fee of the extrinsic from the caller's
+///   balance in the `currency` pallet before dispatching it, rejecting the extrinsic if the caller
+///   can't pay, and hands the fee over to the configured destination afterwards. Both must be
+///   fields of the `Runtime` struct, and are given together.
+/// - `fn dry_run()` - which dispatches a call on behalf of an account on top of the current state,
+///   and returns its result, events, weight and storage writes as a `support::DryRun`, without
+///   changing the state. The nonce and the fee of the caller are left out.
+/// - `fn validate_transaction()` - which checks whether an extrinsic could be included in a block
+///   on top of the current state, without changing it. Unlike `fn apply_extrinsic()`, a nonce in
+///   the future is valid. The priority of the transaction is the fee it pays, if any.
 /// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
-///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   basic actions like incrementing the block number, checking the block to be executed has a
+///   valid block number, extends the last imported block, fits in the maximum block weight and
+///   commits to its extrinsics and to the resulting state root, and recording the block hash. It
+///   returns a `support::BlockReceipt` with the result, events and weight of every extrinsic, which
+///   the system pallet keeps next to the state for the most recent blocks. The block is executed in
+///   a storage transaction, so an invalid block leaves the state of every pallet untouched.
+/// - `const FAILED_EXTRINSICS` - the `support::FailedExtrinsics` policy of the runtime, which
+///   `fn execute_block()` applies: either failing extrinsics are included in the block, or they make
+///   it invalid. It is `Include` by default, and can be chosen with the attribute, e.g.
+///   `#[macros::runtime(failed_extrinsics = InvalidateBlock)]`.
+/// - `fn initialize_block()` and `fn finalize_block()` - which start and finish a block around its
+///   extrinsics. `fn execute_block()` uses them, and so can anything authoring blocks. They call
+///   the `support::Hooks` of every pallet, including system, in the order the pallets are declared:
+///   `on_initialize` before the extrinsics, then `on_idle` and `on_finalize` after them. This means
+///   every pallet must implement `support::Hooks`, even if it only uses the default functions.
+/// - `fn state_root()` - which computes the root of the storage shared by all pallets.
+/// - `fn checkpoint()` and `fn revert_to()` - which record a checkpoint of the state of all pallets
+///   after the last imported block, and revert the state to the checkpoint of a given block. Only
+///   the last `system::Config::MAX_CHECKPOINTS` checkpoints are retained, and only in memory: they
+///   are not written to the storage backend.
+/// - `fn extrinsics_root()` - which computes the root of the extrinsics of a block.
+/// - `fn describe_error()` - which describes a dispatch error, naming the pallet which returned it
+///   and the error, which also works for errors decoded without their message.
+/// - `fn metadata()` - which describes every pallet, including system: its calls, storage items,
+///   events and errors. Every pallet must have an `Error` enum, and storage items are found by
+///   creating each pallet on top of an empty storage.
+/// - `fn collect_events()` - which moves the events deposited by each pallet into the system
+///   pallet, wrapped in a `RuntimeEvent`. It is called after every extrinsic, so every pallet must
+///   have a `take_events` function.
+///
+/// It also implements `support::Transactional` for the `Runtime`, forwarding to every pallet. This
+/// means every pallet, including system, must implement it. The transactions of the system pallet
+/// cover the storage shared by all pallets, the other pallets only cover what they keep outside of
+/// the storage, e.g. their events.
+///
+/// It also generates `struct RuntimeGenesisConfig`, with a field for the `GenesisConfig<T>` of
+/// every pallet, including system. Each of them must implement `Default`, the serde traits and
+/// `support::BuildGenesisConfig`. Its `fn build()` creates a new `Runtime` with that initial state,
+/// and `fn build_with_storage()` does the same on top of the given `support::Storage`.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
 /// - `enum RuntimeCall` - an "outer"-enum representing the accumulation of all possible calls to
-///   all pallets. The system pallet is not included.
+///   all pallets. The system pallet is not included. It implements `support::Encode` and
+///   `support::Decode`, where each pallet is identified by its position in the `Runtime` struct, so
+///   new pallets should be added at the end to keep the encoding of existing calls stable. Calls
+///   nested in calls, e.g. by sudo, can only be decoded up to `codec::MAX_DECODE_DEPTH` deep. It
+///   also implements `support::GetDispatchInfo`.
+/// - `type RuntimeOrigin` - the `system::RawOrigin` of the calls dispatched by the runtime.
+///   Extrinsics are dispatched with the signed origin of their signer.
+/// - `enum RuntimeEvent` - an "outer"-enum representing the accumulation of all possible events of
+///   all pallets. Every pallet must have an `Event<T>` enum. The system pallet is not included. It
+///   implements `support::Encode` and `support::Decode` like `RuntimeCall`, so events can be
+///   stored in the receipts of blocks.
 /// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
 ///   logic like incrementing the nonce of the user is included in the generated code. The system
-///   pallet is not included.
+///   pallet is not included. `Module` errors returned by a pallet are attributed to the index of
+///   that pallet, unless they are attributed already.
+/// - If the attribute names a sudo pallet, e.g. `#[macros::runtime(sudo = sudo)]`, the call stored
+///   by a successful call of that pallet is taken with `take_call` and dispatched right after it,
+///   in its own storage transaction, and its result is handed back to the pallet with
+///   `note_call_result`.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
@@ -32,3 +128,61 @@ pub fn runtime(
 ) -> proc_macro::TokenStream {
 	runtime::runtime(attr, item)
 }
+
+/// Expand the `Event` enum of a pallet.
+///
+/// This implements `Clone`, `PartialEq`, `Debug`, `support::Encode` and `support::Decode` for the
+/// enum. Unlike the derive macros, the generated implementations only require the types of the
+/// event fields to implement these traits, and not the `T: Config` of the pallet. Each event is
+/// encoded as its position in the enum, followed by its fields. It also generates `fn metadata()` on the enum, which
+/// describes each event: its name, fields and docs.
+///
+/// Every event must either have named fields or no fields at all.
+#[proc_macro_attribute]
+pub fn event(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	event::event(attr, item)
+}
+
+/// Expand the `Error` enum of a pallet.
+///
+/// This generates `fn index()` and `fn name()` on the enum, which identify each error within the
+/// pallet, `fn name_of()`, which names the error at an index, `fn metadata()`, which describes
+/// every error, and implements `From<Error>` for `support::DispatchError`. The index of the pallet
+/// itself is filled in by the `Runtime` when it dispatches a call.
+///
+/// Errors can't have fields, and the enum can't be generic.
+#[proc_macro_attribute]
+pub fn error(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	error::error(attr, item)
+}
+
+/// Derive `support::Encode` for a struct or an enum.
+///
+/// The fields are encoded in declaration order. Enum variants are encoded as their index, followed
+/// by their fields. The index of a variant is its position in the enum, unless it has a
+/// `#[codec(index = ..)]` attribute, so that variants can be added or reordered without changing
+/// the encoding of the other variants.
+///
+/// Every type parameter of the type must implement `support::Encode`.
+#[proc_macro_derive(Encode, attributes(codec))]
+pub fn derive_encode(
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	codec::derive_encode(item)
+}
+
+/// Derive `support::Decode` for a struct or an enum, reading the encoding of `#[derive(Encode)]`.
+///
+/// Every type parameter of the type must implement `support::Decode`.
+#[proc_macro_derive(Decode, attributes(codec))]
+pub fn derive_decode(
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	codec::derive_decode(item)
+}
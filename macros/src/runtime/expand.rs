@@ -3,43 +3,354 @@ use quote::quote;
 
 /// See the `fn runtime` docs at the `lib.rs` of this crate for a high level definition.
 pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
-	let RuntimeDef { runtime_struct, pallets } = def;
+	let RuntimeDef { runtime_struct, pallets, failed_extrinsics, transaction_payment, sudo } = def;
 
 	// This is a vector of all the pallet names, not including system.
 	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+	// This is the index of each pallet, which is used to identify the pallet of an encoded call. The
+	// system pallet has index 0, so the other pallets start at 1.
+	let pallet_index = (1..=pallets.len()).map(|i| i as u8).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
 	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
 
+	// This is a vector of all the pallet names, including system, in the order their hooks are called.
+	let hooked_pallets = std::iter::once(syn::Ident::new("system", proc_macro2::Span::call_site()))
+		.chain(pallet_names.iter().cloned())
+		.collect::<Vec<_>>();
+
+	// If the runtime declares a `transaction_payment` pallet, fees are withdrawn from the caller's
+	// balance in its `currency` pallet before dispatching a call, and handed over afterwards. The
+	// closure checking an extrinsic then returns the fee it withdrew.
+	let (extrinsic_len, withdraw_fee, fee_binding, deposit_fee, priority, checked_fee) = match &transaction_payment {
+		Some((payment, currency)) => (
+			quote! { let len = crate::support::Encode::encode(&extrinsic).len() as u32; },
+			quote! {
+				let fee = runtime.#payment.withdraw_fee(&mut runtime.#currency, &caller, len, info.weight)?;
+			},
+			quote! { let fee = },
+			quote! {
+				let author = self.system.block_author().cloned();
+				self.#payment.deposit_fee(&mut self.#currency, author.as_ref(), fee);
+			},
+			// Transactions which pay a higher fee have a higher priority.
+			quote! {{
+				let len = crate::support::Encode::encode(extrinsic).len() as u32;
+				crate::support::Transactional::start_transaction(self);
+				let fee = self.#payment.withdraw_fee(&mut self.#currency, &extrinsic.signer, len, info.weight);
+				crate::support::Transactional::rollback_transaction(self);
+				num::ToPrimitive::to_u64(&fee?).unwrap_or(crate::support::TransactionPriority::MAX)
+			}},
+			quote! { fee },
+		),
+		None => (quote! {}, quote! {}, quote! {}, quote! {}, quote! { 0 }, quote! { () }),
+	};
+
+	// The `sudo` pallet can't dispatch calls by itself, so after one of its calls the runtime
+	// dispatches the call it stored, in its own storage transaction, and reports the result back to
+	// the pallet.
+	let after_dispatch = pallet_names
+		.iter()
+		.map(|name| match &sudo {
+			Some(sudo) if sudo == name => quote! {
+				if let Some((origin, call)) = self.#sudo.take_call() {
+					let result = crate::support::with_transaction(self, |runtime| runtime.dispatch(origin, call));
+					self.#sudo.note_call_result(result);
+				}
+			},
+			_ => quote! {},
+		})
+		.collect::<Vec<_>>();
+
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
 			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
 			fn new() -> Self {
+				Self::with_storage(crate::support::Storage::default())
+			}
+
+			// Create a new instance of the main Runtime, where every pallet uses `storage`.
+			fn with_storage(storage: crate::support::Storage) -> Self {
 				Self {
 					// Since system is not included in the list of pallets, we manually add it here.
-					system: <system::Pallet::<Self>>::new(),
+					system: <system::Pallet::<Self>>::new(&storage),
 					#(
-						#pallet_names: <#pallet_types>::new()
+						#pallet_names: <#pallet_types>::new(&storage)
 					),*
 				}
 			}
 
+			// Describe every pallet of the runtime, including system, for front-ends and clients.
+			//
+			// The storage items of each pallet are found by creating the pallet on top of an empty
+			// storage, see `support::metadata::storage_metadata`.
+			pub fn metadata() -> crate::support::metadata::RuntimeMetadata {
+				crate::support::metadata::RuntimeMetadata {
+					pallets: vec![
+						// Since system is not included in the list of pallets, we manually add it here.
+						crate::support::metadata::PalletMetadata {
+							name: "system".to_string(),
+							index: 0,
+							calls: vec![],
+							storage: crate::support::metadata::storage_metadata(|storage| {
+								<system::Pallet::<Self>>::new(storage);
+							}),
+							events: vec![],
+							errors: vec![],
+						},
+						#(
+							crate::support::metadata::PalletMetadata {
+								name: stringify!(#pallet_names).to_string(),
+								index: #pallet_index,
+								calls: #pallet_names::Call::<Self>::metadata(),
+								storage: crate::support::metadata::storage_metadata(|storage| {
+									<#pallet_types>::new(storage);
+								}),
+								events: #pallet_names::Event::<Self>::metadata(),
+								errors: #pallet_names::Error::metadata(),
+							},
+						)*
+					],
+				}
+			}
+
+			// Dispatch `call` on behalf of `caller` on top of the current state, and return what it
+			// would do, without changing the state. Without a caller, `call` is dispatched with the
+			// `None` origin, as an unsigned call would be.
+			//
+			// Unlike `apply_extrinsic`, this does not check or increment the nonce of the caller, and
+			// does not charge any fee.
+			fn dry_run(&mut self, caller: Option<types::AccountId>, call: RuntimeCall) -> crate::support::DryRun<RuntimeEvent> {
+				let weight = crate::support::GetDispatchInfo::get_dispatch_info(&call).weight;
+				let events_before = self.system.events().len();
+				// Everything happens in a storage transaction, which is rolled back at the end.
+				crate::support::Transactional::start_transaction(self);
+				let origin = match caller {
+					Some(caller) => system::RawOrigin::Signed(caller),
+					None => system::RawOrigin::None,
+				};
+				let result = crate::support::with_transaction(self, |runtime| runtime.dispatch(origin.into(), call));
+				self.collect_events();
+				let events = self.system.events()[events_before..].iter().map(|record| record.event.clone()).collect();
+				let storage_changes = self.system.storage().overlay_changes();
+				crate::support::Transactional::rollback_transaction(self);
+				crate::support::DryRun { result, events, weight, storage_changes }
+			}
+
+			// Record a checkpoint of the state of every pallet after the last imported block, see
+			// `system::Pallet::checkpoint`. Checkpoints are only kept in memory: they are not written
+			// to the storage backend, so they are lost when the node restarts.
+			fn checkpoint(&mut self) {
+				self.system.checkpoint();
+			}
+
+			// Revert the state of every pallet to the retained checkpoint of block `block_number`,
+			// returning whether there was one.
+			fn revert_to(&mut self, block_number: types::BlockNumber) -> bool {
+				self.system.revert_to(block_number)
+			}
+
+			// The root of all the runtime storage, which is shared by every pallet.
+			fn state_root(&self) -> <Self as system::Config>::Hash {
+				self.system.storage().root::<<Self as system::Config>::Hashing>()
+			}
+
+			// Apply a single extrinsic on top of the current state.
+			//
+			// The extrinsic is rejected without touching the state if its signature is invalid, its
+			// nonce is not the caller's next nonce, its weight does not fit in the block, or the
+			// caller can't pay its fee. Otherwise the nonce is incremented, and the call is
+			// dispatched in its own storage transaction, so that a failing call does not leave any
+			// of its writes behind. The fee is charged either way.
+			fn apply_extrinsic(&mut self, extrinsic: types::Extrinsic) -> crate::support::ApplyExtrinsicResult {
+				self.system.note_extrinsic();
+				#extrinsic_len
+				let support::Extrinsic { signer, signature, nonce, call } = extrinsic;
+				let payload = types::Extrinsic::signing_payload(&call, &nonce);
+				if !crate::support::Verify::verify(&signature, &payload, &signer) {
+					return Err(crate::support::InvalidTransaction::BadProof)
+				}
+				// The signer is only trusted as the caller once the signature has been checked.
+				let caller = signer;
+				let info = crate::support::GetDispatchInfo::get_dispatch_info(&call);
+				// The checks run in a storage transaction, so a rejected extrinsic leaves nothing behind.
+				#fee_binding crate::support::with_transaction(self, |runtime| {
+					runtime.system.check_nonce(&caller, nonce)?;
+					runtime.system.consume_weight(info.weight)?;
+					#withdraw_fee
+					runtime.system.inc_nonce(&caller);
+					Ok::<_, crate::support::InvalidTransaction>(#checked_fee)
+				})?;
+				let origin = system::RawOrigin::Signed(caller).into();
+				let result = crate::support::with_transaction(self, |runtime| runtime.dispatch(origin, call));
+				#deposit_fee
+				self.collect_events();
+				Ok(result)
+			}
+
+			// Check whether an extrinsic could be included in a block on top of the current state,
+			// possibly after other extrinsics of the same signer, without changing the state.
+			//
+			// Unlike `apply_extrinsic`, a nonce in the future is valid. The fee is checked against the
+			// current balance of the signer, even if earlier extrinsics of the signer will pay fees too.
+			fn validate_transaction(&mut self, extrinsic: &types::Extrinsic) -> crate::support::TransactionValidity {
+				let payload = types::Extrinsic::signing_payload(&extrinsic.call, &extrinsic.nonce);
+				if !crate::support::Verify::verify(&extrinsic.signature, &payload, &extrinsic.signer) {
+					return Err(crate::support::InvalidTransaction::BadProof)
+				}
+				if extrinsic.nonce < self.system.account_nonce(&extrinsic.signer) {
+					return Err(crate::support::InvalidTransaction::Stale)
+				}
+				let info = crate::support::GetDispatchInfo::get_dispatch_info(&extrinsic.call);
+				if info.weight > <Self as system::Config>::MAX_BLOCK_WEIGHT {
+					return Err(crate::support::InvalidTransaction::ExhaustsResources)
+				}
+				let priority = #priority;
+				Ok(crate::support::ValidTransaction { priority })
+			}
+
+			// Describe a dispatch error, naming the pallet which returned it, if any.
+			fn describe_error(error: &crate::support::DispatchError) -> String {
+				match error {
+					#(
+						crate::support::DispatchError::Module(crate::support::ModuleError {
+							pallet_index: Some(#pallet_index),
+							error_index,
+							..
+						}) => match #pallet_names::Error::name_of(*error_index) {
+							Some(name) => format!("{}::{}", stringify!(#pallet_names), name),
+							None => error.to_string(),
+						},
+					)*
+					_ => error.to_string(),
+				}
+			}
+
+			// Move the events deposited by each pallet into the system pallet.
+			fn collect_events(&mut self) {
+				#(
+					for event in self.#pallet_names.take_events() {
+						self.system.deposit_event(RuntimeEvent::#pallet_names(event));
+					}
+				)*
+			}
+
+			// The root of the extrinsics of a block, which the header commits to.
+			fn extrinsics_root(extrinsics: &[types::Extrinsic]) -> <Self as system::Config>::Hash {
+				crate::support::crypto::merkle_root::<<Self as system::Config>::Hashing>(
+					extrinsics.iter().map(crate::support::Encode::encode),
+				)
+			}
+
+			// Start a new block authored by `author`, on top of the last imported block, and run the
+			// `on_initialize` hook of every pallet.
+			fn initialize_block(&mut self, author: Option<<Self as system::Config>::AccountId>) {
+				self.system.initialize_block(author);
+				let block_number = self.system.block_number();
+				#(
+					let weight = crate::support::Hooks::on_initialize(&mut self.#hooked_pallets, block_number);
+					self.system.register_weight(weight);
+				)*
+				self.collect_events();
+			}
+
+			// Finish the current block, after all of its extrinsics have been applied, by running the
+			// `on_idle` and then the `on_finalize` hook of every pallet.
+			fn finalize_block(&mut self) {
+				self.system.note_finished_extrinsics();
+				let block_number = self.system.block_number();
+				#(
+					let remaining_weight =
+						<Self as system::Config>::MAX_BLOCK_WEIGHT.saturating_sub(self.system.block_weight());
+					let weight = crate::support::Hooks::on_idle(&mut self.#hooked_pallets, remaining_weight);
+					self.system.register_weight(weight);
+				)*
+				#( crate::support::Hooks::on_finalize(&mut self.#hooked_pallets, block_number); )*
+				self.collect_events();
+			}
+
+			// Whether a failing extrinsic invalidates the block it is in, chosen with
+			// `#[macros::runtime(failed_extrinsics = ..)]`.
+			const FAILED_EXTRINSICS: crate::support::FailedExtrinsics =
+				crate::support::FailedExtrinsics::#failed_extrinsics;
+
 			// Execute a block of extrinsics. Increments the block number.
-			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
-				self.system.inc_block_number();
-				if block.header.block_number != self.system.block_number() {
-					return Err(&"block number does not match what is expected")
-				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
-					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
-					});
+			//
+			// The block must build on the last imported block, so its parent hash must match the
+			// hash of the last imported header, and the total weight of its calls must not exceed
+			// `MAX_BLOCK_WEIGHT`. The header must commit to the extrinsics of the block, and after
+			// executing them, to the resulting state root. Its extrinsics must be allowed by
+			// `FAILED_EXTRINSICS`.
+			//
+			// The block is executed in a storage transaction, so an invalid block changes nothing.
+			// The receipt of a valid block is returned, and stored by the system pallet once the
+			// transaction is committed, see `system::Pallet::block_receipt`.
+			fn execute_block(
+				&mut self,
+				block: types::Block,
+			) -> Result<crate::support::BlockReceipt<RuntimeEvent>, crate::support::DispatchError> {
+				if block.header.parent_hash != self.system.parent_hash() {
+					return Err(crate::support::DispatchError::Other("parent hash does not match the last imported block"))
 				}
-				Ok(())
+				let block_weight = block.extrinsics.iter().fold(0, |weight: crate::support::Weight, extrinsic| {
+					weight.saturating_add(crate::support::GetDispatchInfo::get_dispatch_info(&extrinsic.call).weight)
+				});
+				if block_weight > <Self as system::Config>::MAX_BLOCK_WEIGHT {
+					return Err(crate::support::DispatchError::Other("block weight exceeds the maximum block weight"))
+				}
+				if block.header.extrinsics_root != Self::extrinsics_root(&block.extrinsics) {
+					return Err(crate::support::DispatchError::Other("extrinsics root does not match the extrinsics"))
+				}
+				let receipt = crate::support::with_transaction(self, |runtime| {
+					runtime.initialize_block(block.header.author.clone());
+					if block.header.block_number != runtime.system.block_number() {
+						return Err(crate::support::DispatchError::Other("block number does not match what is expected"))
+					}
+					let mut receipt = crate::support::BlockReceipt { extrinsics: Vec::new() };
+					for extrinsic in block.extrinsics {
+						let (events_before, weight_before) = (runtime.system.events().len(), runtime.system.block_weight());
+						let result = runtime.apply_extrinsic(extrinsic);
+						if !Self::FAILED_EXTRINSICS.allows(&result) {
+							return Err(crate::support::DispatchError::Other("an extrinsic of the block failed"))
+						}
+						receipt.extrinsics.push(crate::support::ExtrinsicReceipt {
+							result,
+							events: runtime.system.events()[events_before..].iter().map(|record| record.event.clone()).collect(),
+							weight: runtime.system.block_weight() - weight_before,
+						});
+					}
+					runtime.finalize_block();
+					if block.header.state_root != runtime.state_root() {
+						return Err(crate::support::DispatchError::Other(
+							"state root does not match the state after executing the block",
+						))
+					}
+					runtime.system.note_block_hash(block.header.hash::<<Self as system::Config>::Hashing>());
+					Ok(receipt)
+				})?;
+				self.system.note_receipt(receipt.clone());
+				Ok(receipt)
+			}
+		}
+	};
+
+	// This quote block implements `Transactional` for the `Runtime` by forwarding to every pallet,
+	// including system, whose transactions cover the storage shared by every pallet.
+	let transactional_impl = quote! {
+		impl crate::support::Transactional for #runtime_struct {
+			fn start_transaction(&mut self) {
+				self.system.start_transaction();
+				#( self.#pallet_names.start_transaction(); )*
+			}
+
+			fn commit_transaction(&mut self) {
+				self.system.commit_transaction();
+				#( self.#pallet_names.commit_transaction(); )*
+			}
+
+			fn rollback_transaction(&mut self) {
+				self.system.rollback_transaction();
+				#( self.#pallet_names.rollback_transaction(); )*
 			}
 		}
 	};
@@ -51,21 +362,99 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(Clone)]
 		pub enum RuntimeCall {
 			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
 		}
 
+		// The origin of the calls dispatched by the runtime.
+		pub type RuntimeOrigin = system::RawOrigin<<#runtime_struct as system::Config>::AccountId>;
+
+		// These are all the events which can be deposited by the pallets.
+		// Note that it is just an accumulation of the events of each pallet.
+		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, PartialEq)]
+		pub enum RuntimeEvent {
+			#( #pallet_names(#pallet_names::Event<#runtime_struct>) ),*
+		}
+
+		// Encode a `RuntimeCall` as the index of its pallet, followed by the encoded pallet call.
+		impl crate::support::Encode for RuntimeCall {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							dest.push(#pallet_index);
+							crate::support::Encode::encode_to(call, dest);
+						}
+					),*
+				}
+			}
+		}
+
+		// Decode a `RuntimeCall` from the encoding of `Encode`.
+		//
+		// Calls can hold other calls, e.g. the calls of sudo, so their nesting is limited to keep a
+		// malicious encoding from overflowing the stack.
+		impl crate::support::Decode for RuntimeCall {
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				crate::support::codec::decode_nested(|| match <u8 as crate::support::Decode>::decode(input)? {
+					#(
+						#pallet_index => Ok(RuntimeCall::#pallet_names(crate::support::Decode::decode(input)?)),
+					)*
+					_ => Err(crate::support::codec::Error("unknown pallet index")),
+				})
+			}
+		}
+
+		// Encode a `RuntimeEvent` like a `RuntimeCall`, as the index of its pallet followed by the
+		// encoded pallet event.
+		impl crate::support::Encode for RuntimeEvent {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						RuntimeEvent::#pallet_names(event) => {
+							dest.push(#pallet_index);
+							crate::support::Encode::encode_to(event, dest);
+						}
+					),*
+				}
+			}
+		}
+
+		// Decode a `RuntimeEvent` from the encoding of `Encode`.
+		impl crate::support::Decode for RuntimeEvent {
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+				match <u8 as crate::support::Decode>::decode(input)? {
+					#(
+						#pallet_index => Ok(RuntimeEvent::#pallet_names(crate::support::Decode::decode(input)?)),
+					)*
+					_ => Err(crate::support::codec::Error("unknown pallet index")),
+				}
+			}
+		}
+
+		impl crate::support::GetDispatchInfo for RuntimeCall {
+			fn get_dispatch_info(&self) -> crate::support::DispatchInfo {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => crate::support::GetDispatchInfo::get_dispatch_info(call),
+					)*
+				}
+			}
+		}
+
 		impl crate::support::Dispatch for #runtime_struct {
-			type Caller = <Runtime as system::Config>::AccountId;
+			type Origin = RuntimeOrigin;
 			type Call = RuntimeCall;
-			// Dispatch a call on behalf of a caller. Increments the caller's nonce.
+			// Dispatch a call on behalf of an origin.
 			//
 			// Dispatch allows us to identify which underlying pallet call we want to execute.
-			// Note that we extract the `caller` from the extrinsic, and use that information
-			// to determine who we are executing the call on behalf of.
+			// Note that for extrinsics, the origin is the signer of the extrinsic, which
+			// determines who we are executing the call on behalf of.
 			fn dispatch(
 				&mut self,
-				caller: Self::Caller,
+				origin: Self::Origin,
 				runtime_call: Self::Call,
 			) -> crate::support::DispatchResult {
 				// This match statement will allow us to correctly route `RuntimeCall`s
@@ -73,7 +462,8 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 				match runtime_call {
 					#(
 						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
+							self.#pallet_names.dispatch(origin, call).map_err(|e| e.in_pallet(#pallet_index))?;
+							#after_dispatch
 						}
 					),*
 				}
@@ -82,10 +472,43 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		}
 	};
 
+	// This quote block creates the `RuntimeGenesisConfig`, which has the `GenesisConfig` of every
+	// pallet, including system, and can build a new `Runtime` from them.
+	let genesis_config_impl = quote! {
+		// The initial state of the whole runtime, e.g. as loaded from a chain spec.
+		//
+		// Pallets which are missing from the JSON representation start with their default genesis
+		// config.
+		#[derive(Default, serde::Serialize, serde::Deserialize)]
+		#[serde(default, deny_unknown_fields)]
+		pub struct RuntimeGenesisConfig {
+			pub system: system::GenesisConfig<#runtime_struct>,
+			#( pub #pallet_names: #pallet_names::GenesisConfig<#runtime_struct>, )*
+		}
+
+		impl RuntimeGenesisConfig {
+			// Create a new `Runtime` with this initial state.
+			pub fn build(&self) -> #runtime_struct {
+				self.build_with_storage(crate::support::Storage::default())
+			}
+
+			// Create a new `Runtime` with this initial state, on top of `storage`, which should not
+			// hold a chain yet.
+			pub fn build_with_storage(&self, storage: crate::support::Storage) -> #runtime_struct {
+				let mut runtime = #runtime_struct::with_storage(storage);
+				crate::support::BuildGenesisConfig::build(&self.system, &mut runtime.system);
+				#( crate::support::BuildGenesisConfig::build(&self.#pallet_names, &mut runtime.#pallet_names); )*
+				runtime
+			}
+		}
+	};
+
 	// We combine and return all the generated code.
 	quote! {
 		#dispatch_impl
+		#genesis_config_impl
 		#runtime_impl
+		#transactional_impl
 	}
 	.into()
 }
@@ -1,5 +1,13 @@
 use syn::spanned::Spanned;
 
+// Custom keywords we match to when parsing the `#[macros::runtime(..)]` attribute.
+mod keyword {
+	syn::custom_keyword!(failed_extrinsics);
+	syn::custom_keyword!(transaction_payment);
+	syn::custom_keyword!(currency);
+	syn::custom_keyword!(sudo);
+}
+
 /// This object will collect all the information we need to keep while parsing the `Runtime` struct.
 #[derive(Debug)]
 pub struct RuntimeDef {
@@ -8,10 +16,32 @@ pub struct RuntimeDef {
 	/// This is the list of pallets included in the `Runtime` struct. We omit `system` from this
 	/// list, but during parsing we check that system exists.
 	pub pallets: Vec<(syn::Ident, syn::Type)>,
+	/// The variant of `support::FailedExtrinsics` chosen with
+	/// `#[macros::runtime(failed_extrinsics = ..)]`, which is `Include` by default.
+	pub failed_extrinsics: syn::Ident,
+	/// The fields of the pallet charging the fees of extrinsics and of the pallet holding the
+	/// balances they are paid from, chosen with
+	/// `#[macros::runtime(transaction_payment = .., currency = ..)]`. No fees are charged without
+	/// them.
+	pub transaction_payment: Option<(syn::Ident, syn::Ident)>,
+	/// The field of the pallet storing calls to dispatch on behalf of another origin, chosen with
+	/// `#[macros::runtime(sudo = ..)]`.
+	pub sudo: Option<syn::Ident>,
+}
+
+/// The arguments of `#[macros::runtime(..)]`, each of the form `name = value`.
+#[derive(Default)]
+struct RuntimeAttr {
+	failed_extrinsics: Option<syn::Ident>,
+	transaction_payment: Option<syn::Ident>,
+	currency: Option<syn::Ident>,
+	sudo: Option<syn::Ident>,
 }
 
 impl RuntimeDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	pub fn try_from(attr: proc_macro2::TokenStream, item: syn::Item) -> syn::Result<Self> {
+		let attr = syn::parse::Parser::parse2(parse_attr, attr)?;
+
 		// First we check that we are parsing a `struct`.
 		let item_struct = if let syn::Item::Struct(item) = item {
 			item
@@ -33,8 +63,61 @@ impl RuntimeDef {
 			}
 		}
 
-		Ok(Self { runtime_struct, pallets })
+		// The pallets named by the attribute must be fields of the `Runtime` struct.
+		let check_pallet = |name: syn::Ident| {
+			if pallets.iter().any(|(pallet, _)| *pallet == name) {
+				Ok(name)
+			} else {
+				let msg = format!("Invalid runtime, `{}` is not a pallet of the runtime", name);
+				Err(syn::Error::new(name.span(), msg))
+			}
+		};
+		let transaction_payment = match (attr.transaction_payment, attr.currency) {
+			(Some(transaction_payment), Some(currency)) => {
+				Some((check_pallet(transaction_payment)?, check_pallet(currency)?))
+			},
+			(None, None) => None,
+			(Some(name), None) | (None, Some(name)) => {
+				let msg = "Invalid runtime, `transaction_payment` and `currency` must be given together";
+				return Err(syn::Error::new(name.span(), msg))
+			},
+		};
+		let sudo = attr.sudo.map(check_pallet).transpose()?;
+		let failed_extrinsics = attr
+			.failed_extrinsics
+			.unwrap_or_else(|| syn::Ident::new("Include", proc_macro2::Span::call_site()));
+
+		Ok(Self { runtime_struct, pallets, failed_extrinsics, transaction_payment, sudo })
+	}
+}
+
+/// Parse the contents of `#[macros::runtime(..)]`, a comma separated list of `name = value`
+/// arguments, each of which can be given at most once.
+fn parse_attr(input: syn::parse::ParseStream) -> syn::Result<RuntimeAttr> {
+	let mut attr = RuntimeAttr::default();
+	while !input.is_empty() {
+		let lookahead = input.lookahead1();
+		let (span, value) = if lookahead.peek(keyword::failed_extrinsics) {
+			(input.parse::<keyword::failed_extrinsics>()?.span, &mut attr.failed_extrinsics)
+		} else if lookahead.peek(keyword::transaction_payment) {
+			(input.parse::<keyword::transaction_payment>()?.span, &mut attr.transaction_payment)
+		} else if lookahead.peek(keyword::currency) {
+			(input.parse::<keyword::currency>()?.span, &mut attr.currency)
+		} else if lookahead.peek(keyword::sudo) {
+			(input.parse::<keyword::sudo>()?.span, &mut attr.sudo)
+		} else {
+			return Err(lookahead.error())
+		};
+		if value.is_some() {
+			return Err(syn::Error::new(span, "Invalid runtime, duplicate argument"))
+		}
+		input.parse::<syn::Token![=]>()?;
+		*value = Some(input.parse()?);
+		if !input.is_empty() {
+			input.parse::<syn::Token![,]>()?;
+		}
 	}
+	Ok(attr)
 }
 
 /// This function checks that the `system` pallet is the first pallet included in the `Runtime`
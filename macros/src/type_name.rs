@@ -0,0 +1,25 @@
+/// The name of `ty` as it is written in the source, e.g. `Box<T::RuntimeCall>`, which the macros
+/// include in the runtime metadata.
+///
+/// The tokens of `ty` are joined without the spaces `to_string` puts between all of them, only
+/// keeping a space between two words, e.g. in `&'static str`, and after a comma.
+pub fn type_name(ty: &syn::Type) -> String {
+	let tokens = quote::quote!(#ty).to_string();
+	let is_word = |c: char| c.is_alphanumeric() || c == '_';
+	let mut name = String::with_capacity(tokens.len());
+	let mut chars = tokens.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c != ' ' {
+			name.push(c);
+			continue
+		}
+		let before = name.chars().last();
+		let after = chars.peek().copied();
+		match (before, after) {
+			(Some(','), _) => name.push(' '),
+			(Some(before), Some(after)) if is_word(before) && (is_word(after) || after == '\'') => name.push(' '),
+			_ => {},
+		}
+	}
+	name
+}
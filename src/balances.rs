@@ -1,19 +1,79 @@
-use std::collections::BTreeMap;
-
 use num::{CheckedAdd, CheckedSub, Zero};
 
+use crate::support::{
+	storage::storage_item, ArithmeticError, BuildGenesisConfig, Decode, Encode, Hooks, Storage, StorageMap,
+	Transactional, TransactionalValue,
+};
+
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-	balances: BTreeMap<T::AccountId, T::Balance>,
+	balances: StorageMap<T::AccountId, T::Balance>,
+	/// Events deposited by this pallet, which are yet to be collected by the runtime.
+	events: TransactionalValue<Vec<Event<T>>>,
+}
+
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `amount` was transferred from `from` to `to`.
+	Transfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+	/// The balance of `who` was set to `amount` by the root origin.
+	BalanceSet { who: T::AccountId, amount: T::Balance },
+}
+
+/// The initial state of the balances pallet.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(
+	bound(
+		serialize = "T::AccountId: serde::Serialize, T::Balance: serde::Serialize",
+		deserialize = "T::AccountId: serde::Deserialize<'de>, T::Balance: serde::Deserialize<'de>"
+	),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The initial balance of each account.
+	pub balances: Vec<(T::AccountId, T::Balance)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { balances: Vec::new() }
+	}
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+	fn build(&self, pallet: &mut Pallet<T>) {
+		for (who, amount) in &self.balances {
+			pallet.set_balance(who, *amount);
+		}
+	}
 }
 
 pub trait Config: crate::system::Config {
-	type Balance: Zero + CheckedSub + CheckedAdd + Copy;
+	type Balance: Zero + CheckedSub + CheckedAdd + Copy + Encode + Decode;
+}
+
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The account does not have enough funds for the operation.
+	InsufficientBalance,
 }
 
 impl<T: Config> Pallet<T> {
-	pub fn new() -> Self {
-		Self { balances: BTreeMap::new() }
+	pub fn new(storage: &Storage) -> Self {
+		Self {
+			balances: storage_item!(StorageMap<T::AccountId, T::Balance>, storage, "Balances", "Balances"),
+			events: TransactionalValue::new(Vec::new()),
+		}
+	}
+
+	pub fn deposit_event(&mut self, event: Event<T>) {
+		self.events.get_mut().push(event);
+	}
+
+	/// Take all the events deposited so far, see `system::Pallet::deposit_event`.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		std::mem::take(self.events.get_mut())
 	}
 
 	pub fn set_balance(&mut self, who: &T::AccountId, amount: T::Balance) {
@@ -21,12 +81,45 @@ impl<T: Config> Pallet<T> {
 	}
 
 	pub fn balance(&self, who: &T::AccountId) -> T::Balance {
-		*self.balances.get(who).unwrap_or(&T::Balance::zero())
+		self.balances.get(who).unwrap_or_else(T::Balance::zero)
+	}
+
+	/// Take `amount` out of the balance of `who`, e.g. to pay a fee.
+	pub fn withdraw(&mut self, who: &T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+		let new_balance = self.balance(who).checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+		self.set_balance(who, new_balance);
+		Ok(())
+	}
+
+	/// Add `amount` to the balance of `who`.
+	pub fn deposit(&mut self, who: &T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+		let new_balance = self.balance(who).checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
+		self.set_balance(who, new_balance);
+		Ok(())
+	}
+}
+
+impl<T: Config> Transactional for Pallet<T> {
+	fn start_transaction(&mut self) {
+		self.events.start_transaction();
+	}
+
+	fn commit_transaction(&mut self) {
+		self.events.commit_transaction();
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.events.rollback_transaction();
 	}
 }
 
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
 #[macros::call]
 impl<T: Config> Pallet<T> {
+	/// Transfer `amount` from the caller to `to`.
+	#[call_index(0)]
+	#[weight(10_000)]
 	pub fn transfer(
 		&mut self,
 		caller: T::AccountId,
@@ -36,12 +129,31 @@ impl<T: Config> Pallet<T> {
 		let caller_balance = self.balance(&caller);
 		let to_balance = self.balance(&to);
 
-		let new_caller_balance = caller_balance.checked_sub(&amount).ok_or("Not enough funds.")?;
-		let new_to_balance = to_balance.checked_add(&amount).ok_or("Not enough funds.")?;
+		let new_caller_balance = caller_balance.checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+		let new_to_balance = to_balance.checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
 
 		self.set_balance(&caller, new_caller_balance);
 		self.set_balance(&to, new_to_balance);
 
+		self.deposit_event(Event::Transfer { from: caller, to, amount });
+
+		Ok(())
+	}
+
+	/// Set the balance of `who` to `amount`. This can only be called by the root origin.
+	#[call_index(1)]
+	#[weight(5_000)]
+	pub fn force_set_balance(
+		&mut self,
+		origin: T::RuntimeOrigin,
+		who: T::AccountId,
+		amount: T::Balance,
+	) -> crate::support::DispatchResult {
+		crate::system::ensure_root::<T>(origin)?;
+
+		self.set_balance(&who, amount);
+		self.deposit_event(Event::BalanceSet { who, amount });
+
 		Ok(())
 	}
 }
@@ -56,13 +168,23 @@ mod tests {
 
 	impl crate::system::Config for TestConfig {
 		type AccountId = String;
+		type Signature = crate::support::crypto::TestSignature<String>;
 		type Nonce = u32;
 		type BlockNumber = u32;
+		type Hash = crate::support::crypto::H256;
+		type Hashing = crate::support::crypto::Sha256;
+		type RuntimeOrigin = crate::system::RawOrigin<Self::AccountId>;
+		type RuntimeEvent = ();
+
+		const BLOCK_HASH_COUNT: u32 = 250;
+		const RECEIPT_COUNT: u32 = 250;
+		const MAX_BLOCK_WEIGHT: u64 = 1_000_000;
+		const MAX_CHECKPOINTS: u32 = 0;
 	}
 
 	#[test]
 	fn init_balances() {
-		let mut balances = super::Pallet::<TestConfig>::new();
+		let mut balances = super::Pallet::<TestConfig>::new(&Default::default());
 
 		assert_eq!(balances.balance(&"alice".to_string()), 0);
 		balances.set_balance(&"alice".to_string(), 100);
@@ -70,17 +192,54 @@ mod tests {
 		assert_eq!(balances.balance(&"bob".to_string()), 0);
 	}
 
+	#[test]
+	fn genesis_config() {
+		let mut balances = super::Pallet::<TestConfig>::new(&Default::default());
+		let genesis = super::GenesisConfig::<TestConfig> { balances: vec![("alice".to_string(), 100)] };
+		crate::support::BuildGenesisConfig::build(&genesis, &mut balances);
+
+		assert_eq!(balances.balance(&"alice".to_string()), 100);
+		assert_eq!(balances.balance(&"bob".to_string()), 0);
+	}
+
 	#[test]
 	fn transfer_balance() {
-		let mut balances = super::Pallet::<TestConfig>::new();
+		let mut balances = super::Pallet::<TestConfig>::new(&Default::default());
 
-		assert_eq!(balances.transfer("alice".to_string(), "bob".to_string(), 100), Err("Not enough funds."));
+		assert_eq!(
+			balances.transfer("alice".to_string(), "bob".to_string(), 100),
+			Err(super::Error::InsufficientBalance.into())
+		);
 
 		balances.set_balance(&"alice".to_string(), 100);
 		assert_eq!(balances.transfer("alice".to_string(), "bob".to_string(), 10), Ok(()));
 		assert_eq!(balances.balance(&"alice".to_string()), 90);
 		assert_eq!(balances.balance(&"bob".to_string()), 10);
+		assert_eq!(
+			balances.take_events(),
+			vec![super::Event::Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: 10 }]
+		);
+
+		assert_eq!(
+			balances.transfer("alice".to_string(), "bob".to_string(), 91),
+			Err(super::Error::InsufficientBalance.into())
+		);
+	}
 
-		assert_eq!(balances.transfer("alice".to_string(), "bob".to_string(), 91), Err("Not enough funds."));
+	#[test]
+	fn force_set_balance() {
+		use crate::{support::DispatchError, system::RawOrigin};
+
+		let mut balances = super::Pallet::<TestConfig>::new(&Default::default());
+
+		assert_eq!(
+			balances.force_set_balance(RawOrigin::Signed("alice".to_string()), "alice".to_string(), 100),
+			Err(DispatchError::BadOrigin)
+		);
+		assert_eq!(balances.balance(&"alice".to_string()), 0);
+
+		assert_eq!(balances.force_set_balance(RawOrigin::Root, "alice".to_string(), 100), Ok(()));
+		assert_eq!(balances.balance(&"alice".to_string()), 100);
+		assert_eq!(balances.take_events(), vec![super::Event::BalanceSet { who: "alice".to_string(), amount: 100 }]);
 	}
 }
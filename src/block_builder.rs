@@ -0,0 +1,221 @@
+use crate::{
+	support::{self, ApplyExtrinsicResult, InvalidTransaction, Transactional},
+	types, Runtime,
+};
+
+/// Builds a new block on top of the current state of a `Runtime`.
+///
+/// Extrinsics are applied one by one, and only the ones which are not rejected are included in the
+/// block. The state of the runtime is left untouched: the block is built in a storage transaction
+/// which is rolled back when the builder is dropped. The block can then be imported with
+/// `Runtime::execute_block`, like on any other node.
+pub struct BlockBuilder<'a> {
+	runtime: &'a mut Runtime,
+	parent_hash: types::Hash,
+	author: Option<types::AccountId>,
+	extrinsics: Vec<types::Extrinsic>,
+}
+
+impl<'a> BlockBuilder<'a> {
+	/// Start a block authored by `author` on top of the last block imported by `runtime`.
+	pub fn new(runtime: &'a mut Runtime, author: Option<types::AccountId>) -> Self {
+		let parent_hash = runtime.system.parent_hash();
+		runtime.start_transaction();
+		runtime.initialize_block(author);
+		Self { runtime, parent_hash, author, extrinsics: Vec::new() }
+	}
+
+	/// Apply `extrinsic` on top of the block built so far, and include it in the block unless it is
+	/// rejected, or its call fails and the runtime does not include failing extrinsics, see
+	/// `support::FailedExtrinsics`. An extrinsic which is not included leaves no trace in the block.
+	pub fn push(&mut self, extrinsic: types::Extrinsic) -> ApplyExtrinsicResult {
+		let outcome = support::with_transaction(&mut *self.runtime, |runtime| {
+			let result = runtime.apply_extrinsic(extrinsic.clone());
+			if Runtime::FAILED_EXTRINSICS.allows(&result) {
+				Ok(result)
+			} else {
+				Err(result)
+			}
+		});
+		match outcome {
+			Ok(result) => {
+				self.extrinsics.push(extrinsic);
+				result
+			},
+			Err(result) => result,
+		}
+	}
+
+	/// Push `extrinsics` in order, until one of them does not fit in what is left of the block.
+	///
+	/// Returns the outcome of each extrinsic which was pushed, including the one which did not fit.
+	/// The remaining extrinsics are left for a later block.
+	pub fn push_all(&mut self, extrinsics: impl IntoIterator<Item = types::Extrinsic>) -> Vec<ApplyExtrinsicResult> {
+		let mut outcomes = Vec::new();
+		for extrinsic in extrinsics {
+			let outcome = self.push(extrinsic);
+			outcomes.push(outcome);
+			if outcome == Err(InvalidTransaction::ExhaustsResources) {
+				break;
+			}
+		}
+		outcomes
+	}
+
+	/// Finish the block, with a header which commits to its extrinsics and to the resulting state.
+	pub fn build(mut self) -> types::Block {
+		self.runtime.finalize_block();
+		let header = support::Header {
+			parent_hash: self.parent_hash,
+			block_number: self.runtime.system.block_number(),
+			state_root: self.runtime.state_root(),
+			extrinsics_root: Runtime::extrinsics_root(&self.extrinsics),
+			author: self.author,
+		};
+		types::Block { header, extrinsics: std::mem::take(&mut self.extrinsics) }
+	}
+}
+
+impl Drop for BlockBuilder<'_> {
+	fn drop(&mut self) {
+		// Leave the state of the runtime as it was before the block was started.
+		self.runtime.rollback_transaction();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::BlockBuilder;
+	use crate::{
+		balances,
+		support::{
+			crypto::{ed25519::Pair, Sha256},
+			Decode, DispatchError, Encode, FailedExtrinsics, InvalidTransaction,
+		},
+		Runtime, RuntimeCall, RuntimeEvent,
+	};
+
+	fn transfer(from: &Pair, nonce: u32, to: &Pair, amount: u128) -> crate::types::Extrinsic {
+		crate::sign(from, nonce, RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount }))
+	}
+
+	#[test]
+	fn built_block_is_accepted_by_another_node() {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+		let mut author = Runtime::new();
+		let mut other = Runtime::new();
+		author.balances.set_balance(&alice.public(), 100_000);
+		other.balances.set_balance(&alice.public(), 100_000);
+		let root = author.state_root();
+
+		let mut builder = BlockBuilder::new(&mut author, Some(bob.public()));
+		assert_eq!(builder.push(transfer(&alice, 0, &bob, 10)), Ok(Ok(())));
+		// A transaction which can't be applied yet is not included.
+		assert_eq!(builder.push(transfer(&alice, 2, &bob, 10)), Err(InvalidTransaction::Future));
+		// A failing call is included, and its fee is paid.
+		assert!(matches!(builder.push(transfer(&alice, 1, &bob, 1_000_000)), Ok(Err(_))));
+		let block = builder.build();
+
+		// Building the block does not change the state.
+		assert_eq!(author.state_root(), root);
+		assert_eq!(block.header.block_number, 1);
+		assert_eq!(block.extrinsics.len(), 2);
+
+		// The other node receives the block encoded.
+		let block = crate::types::Block::decode_all(&block.encode()).unwrap();
+		let hash = block.header.hash::<Sha256>();
+		let receipt = other.execute_block(block).unwrap();
+		assert_eq!(other.system.parent_hash(), hash);
+		assert_eq!(other.system.account_nonce(&alice.public()), 2);
+
+		// The failing call is recorded in the receipt of the block.
+		assert_eq!(receipt.extrinsics[0].result, Ok(Ok(())));
+		assert_eq!(receipt.extrinsics[0].weight, 10_000);
+		assert!(receipt.extrinsics[0]
+			.events
+			.contains(&RuntimeEvent::balances(balances::Event::Transfer {
+				from: alice.public(),
+				to: bob.public(),
+				amount: 10
+			})));
+		assert!(matches!(receipt.extrinsics[1].result, Ok(Err(_))));
+		assert_eq!(other.system.extrinsic_receipt(1, 1), Some(receipt.extrinsics[1].clone()));
+		assert_eq!(other.system.extrinsic_receipt(1, 2), None);
+	}
+
+	#[test]
+	fn invalid_blocks_change_nothing() {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+		let mut author = Runtime::new();
+		let mut other = Runtime::new();
+		author.balances.set_balance(&alice.public(), 100_000);
+		other.balances.set_balance(&alice.public(), 100_000);
+		let root = other.state_root();
+
+		let mut builder = BlockBuilder::new(&mut author, None);
+		assert_eq!(builder.push(transfer(&alice, 0, &bob, 10)), Ok(Ok(())));
+		let block = builder.build();
+
+		let mut wrong_number = block.clone();
+		wrong_number.header.block_number = 2;
+		assert_eq!(
+			other.execute_block(wrong_number),
+			Err(DispatchError::Other("block number does not match what is expected"))
+		);
+		let mut wrong_root = block.clone();
+		wrong_root.header.state_root = Default::default();
+		assert_eq!(
+			other.execute_block(wrong_root),
+			Err(DispatchError::Other("state root does not match the state after executing the block"))
+		);
+		// An invalid transaction invalidates the block, even though failing extrinsics are included.
+		let mut invalid_extrinsic = block.clone();
+		invalid_extrinsic.extrinsics = vec![transfer(&alice, 1, &bob, 10)];
+		invalid_extrinsic.header.extrinsics_root = Runtime::extrinsics_root(&invalid_extrinsic.extrinsics);
+		assert_eq!(
+			other.execute_block(invalid_extrinsic),
+			Err(DispatchError::Other("an extrinsic of the block failed"))
+		);
+
+		// Neither block left anything behind, so the valid block can still be imported.
+		assert_eq!(other.system.block_number(), 0);
+		assert_eq!(other.state_root(), root);
+		assert!(other.system.events().is_empty());
+		assert_eq!(other.system.block_receipt(1), None);
+		assert!(other.execute_block(block).is_ok());
+		assert_eq!(other.balances.balance(&bob.public()), 10);
+	}
+
+	#[test]
+	fn failed_extrinsics_policy() {
+		let failed = Ok(Err(DispatchError::Other("failed")));
+		assert_eq!(Runtime::FAILED_EXTRINSICS, FailedExtrinsics::Include);
+		assert!(FailedExtrinsics::Include.allows(&Ok(Ok(()))));
+		assert!(FailedExtrinsics::Include.allows(&failed));
+		assert!(!FailedExtrinsics::Include.allows(&Err(InvalidTransaction::BadProof)));
+		assert!(FailedExtrinsics::InvalidateBlock.allows(&Ok(Ok(()))));
+		assert!(!FailedExtrinsics::InvalidateBlock.allows(&failed));
+		assert!(!FailedExtrinsics::InvalidateBlock.allows(&Err(InvalidTransaction::BadProof)));
+	}
+
+	#[test]
+	fn stops_when_the_block_is_full() {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&alice.public(), 10_000_000);
+
+		// Each transfer has a weight of 10_000, so only 100 of them fit in a block.
+		let mut builder = BlockBuilder::new(&mut runtime, None);
+		let outcomes = builder.push_all((0..150).map(|nonce| transfer(&alice, nonce, &bob, 1)));
+		assert_eq!(outcomes.len(), 101);
+		assert_eq!(outcomes[100], Err(InvalidTransaction::ExhaustsResources));
+		let block = builder.build();
+		assert_eq!(block.extrinsics.len(), 100);
+
+		assert!(runtime.execute_block(block).is_ok());
+		assert_eq!(runtime.balances.balance(&bob.public()), 100);
+	}
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+
+use crate::{
+	support::{storage::UndoLog, Decode, DispatchError, Encode, GetDispatchInfo, Weight},
+	system, types, Runtime,
+};
+
+type Hashing = <Runtime as system::Config>::Hashing;
+
+/// Blocks this deep below the best block are final: the tree forgets them, along with their undo
+/// logs and the forks which don't include them, so they can't be reverted anymore.
+pub const FINALITY_DEPTH: types::BlockNumber = 16;
+
+/// The key of the `StoredTree` in the auxiliary data of the storage, see `Storage::get_aux`. The
+/// blocks of the tree and their undo logs are stored at this key, followed by their kind and hash.
+const TREE_KEY: &[u8] = b"BlockTree";
+
+/// What a fork choice rule knows about a chain of the tree, identified by its last block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, macros::Encode, macros::Decode)]
+pub struct ChainInfo {
+	pub number: types::BlockNumber,
+	/// The total weight of the calls in the blocks of the chain, since the tree was created.
+	pub weight: Weight,
+}
+
+/// A rule choosing the best chain among the forks of a `BlockTree`.
+pub trait ForkChoice {
+	/// Whether the chain `candidate` should replace the `best` chain.
+	fn prefers(&self, candidate: &ChainInfo, best: &ChainInfo) -> bool;
+}
+
+/// The chain with the most blocks is the best. On a tie, the best chain stays the same.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestChain;
+
+impl ForkChoice for LongestChain {
+	fn prefers(&self, candidate: &ChainInfo, best: &ChainInfo) -> bool {
+		candidate.number > best.number
+	}
+}
+
+/// The chain with the most weight is the best. On a tie, the best chain stays the same.
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeaviestChain;
+
+impl ForkChoice for HeaviestChain {
+	fn prefers(&self, candidate: &ChainInfo, best: &ChainInfo) -> bool {
+		candidate.weight > best.weight
+	}
+}
+
+/// The outcome of importing a block into a `BlockTree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+	/// The block was executed on top of the best chain, which it now ends.
+	Best,
+	/// The block is on a fork which is not the best chain, so it was not executed yet.
+	Fork,
+	/// The fork of the block became the best chain. The `retracted` blocks of the previous best chain
+	/// were reverted, from the newest one, and the `enacted` blocks of the new best chain were
+	/// executed, from the oldest one.
+	Reorg { retracted: Vec<types::Hash>, enacted: Vec<types::Hash> },
+}
+
+/// The reasons a block can't be imported into a `BlockTree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+	AlreadyImported,
+	/// The parent of the block is not in the tree.
+	UnknownParent,
+	/// The block, or one of its ancestors on its fork, failed to execute. That block and all its
+	/// descendants are removed from the tree, and the best chain is unchanged.
+	InvalidBlock {
+		hash: types::Hash,
+		error: DispatchError,
+	},
+}
+
+impl std::fmt::Display for ImportError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			ImportError::AlreadyImported => write!(f, "the block is already imported"),
+			ImportError::UnknownParent => write!(f, "the parent of the block is unknown"),
+			ImportError::InvalidBlock { hash, error } => {
+				write!(f, "block {:?} is invalid: {}", hash, Runtime::describe_error(error))
+			},
+		}
+	}
+}
+
+struct TreeBlock {
+	block: types::Block,
+	chain: ChainInfo,
+	/// How to revert the state changes of this block, as long as it is part of the best chain.
+	undo_log: Option<UndoLog>,
+}
+
+/// The tree as stored in the auxiliary data of the storage, besides its blocks.
+#[derive(macros::Encode, macros::Decode)]
+struct StoredTree {
+	root: types::Hash,
+	root_chain: ChainInfo,
+	best: types::Hash,
+	/// The hashes of every block of the tree, except its root.
+	blocks: Vec<types::Hash>,
+}
+
+/// A tree of blocks, holding competing forks on top of the state of the runtime.
+///
+/// The runtime always holds the state after the best chain, which is chosen by the `ForkChoice`
+/// rule. Blocks on other forks are only executed once their fork becomes the best chain: the state
+/// is then reverted to the common ancestor of both chains, and the blocks of the new best chain are
+/// executed on top of it. Every block of the best chain keeps an `UndoLog` for this.
+///
+/// The root of the tree is the last block imported by the runtime when the tree is first created,
+/// and it can't be reverted. Once the best chain is more than `FINALITY_DEPTH` blocks longer, the
+/// root moves up the best chain, and the blocks which don't descend from it are forgotten.
+///
+/// The tree is kept in the auxiliary data of the storage of the runtime, so it is flushed together
+/// with the state, and a tree created on top of the same storage later on carries on with it.
+pub struct BlockTree<F: ForkChoice = LongestChain> {
+	runtime: Runtime,
+	fork_choice: F,
+	/// The hash of the root of the tree.
+	root: types::Hash,
+	root_chain: ChainInfo,
+	/// Every block of the tree, except its root.
+	blocks: HashMap<types::Hash, TreeBlock>,
+	/// The last block of the best chain.
+	best: types::Hash,
+}
+
+impl<F: ForkChoice> BlockTree<F> {
+	/// Create the tree on top of `runtime`, carrying on with the tree in its storage if it ends with
+	/// the last block imported by the runtime.
+	pub fn new(runtime: Runtime, fork_choice: F) -> Self {
+		let storage = runtime.system.storage().clone();
+		let stored = storage
+			.get_aux(TREE_KEY)
+			.map(|bytes| StoredTree::decode_all(&bytes).expect("the block tree is stored with a valid encoding"));
+		match stored {
+			Some(stored) if stored.best == runtime.system.parent_hash() => {
+				let blocks = stored
+					.blocks
+					.iter()
+					.map(|hash| {
+						let bytes = storage
+							.get_aux(&aux_key(b"block", hash))
+							.expect("the blocks of the tree are stored");
+						let (block, chain) =
+							Decode::decode_all(&bytes).expect("blocks are stored with a valid encoding");
+						let undo_log = storage.get_aux(&aux_key(b"undo", hash)).map(|bytes| {
+							UndoLog::decode_all(&bytes).expect("undo logs are stored with a valid encoding")
+						});
+						(*hash, TreeBlock { block, chain, undo_log })
+					})
+					.collect();
+				let StoredTree { root, root_chain, best, .. } = stored;
+				Self { runtime, fork_choice, root, root_chain, blocks, best }
+			},
+			stored => {
+				// The state moved on without the stored tree, if any, so its blocks can't be reverted.
+				for hash in stored.iter().flat_map(|stored| &stored.blocks) {
+					storage.remove_aux(&aux_key(b"block", hash));
+					storage.remove_aux(&aux_key(b"undo", hash));
+				}
+				let root = runtime.system.parent_hash();
+				let root_chain = ChainInfo { number: runtime.system.block_number(), weight: 0 };
+				let tree = Self { runtime, fork_choice, root, root_chain, blocks: HashMap::new(), best: root };
+				tree.store();
+				tree
+			},
+		}
+	}
+
+	/// The runtime, holding the state after the best chain.
+	pub fn runtime(&self) -> &Runtime {
+		&self.runtime
+	}
+
+	/// The hash of the last block of the best chain.
+	pub fn best_hash(&self) -> types::Hash {
+		self.best
+	}
+
+	pub fn contains(&self, hash: &types::Hash) -> bool {
+		*hash == self.root || self.blocks.contains_key(hash)
+	}
+
+	/// Import `block` into the tree, and reorganise the state if its fork becomes the best chain.
+	/// The blocks which become final are then forgotten, see `FINALITY_DEPTH`.
+	pub fn import(&mut self, block: types::Block) -> Result<ImportOutcome, ImportError> {
+		let outcome = self.import_block(block);
+		self.prune();
+		self.store();
+		outcome
+	}
+
+	fn import_block(&mut self, block: types::Block) -> Result<ImportOutcome, ImportError> {
+		let hash = block.header.hash::<Hashing>();
+		if self.contains(&hash) {
+			return Err(ImportError::AlreadyImported);
+		}
+		let parent_hash = block.header.parent_hash;
+		let parent = self.chain_info(&parent_hash).ok_or(ImportError::UnknownParent)?;
+		// The fork choice only relies on what is known without executing the block.
+		let weight = block
+			.extrinsics
+			.iter()
+			.fold(parent.weight, |weight, extrinsic| weight.saturating_add(extrinsic.call.get_dispatch_info().weight));
+		let chain = ChainInfo { number: parent.number + 1, weight };
+		self.insert(hash, TreeBlock { block, chain, undo_log: None });
+
+		if parent_hash == self.best {
+			if let Err(error) = self.execute(hash) {
+				self.remove(&hash);
+				return Err(ImportError::InvalidBlock { hash, error });
+			}
+			self.best = hash;
+			Ok(ImportOutcome::Best)
+		} else if self.fork_choice.prefers(&chain, &self.best_chain_info()) {
+			self.reorg(hash)
+		} else {
+			Ok(ImportOutcome::Fork)
+		}
+	}
+
+	fn chain_info(&self, hash: &types::Hash) -> Option<ChainInfo> {
+		if *hash == self.root {
+			return Some(self.root_chain);
+		}
+		self.blocks.get(hash).map(|block| block.chain)
+	}
+
+	fn best_chain_info(&self) -> ChainInfo {
+		self.chain_info(&self.best).expect("the best block is in the tree")
+	}
+
+	/// Make the chain ending with `target` the best chain.
+	fn reorg(&mut self, target: types::Hash) -> Result<ImportOutcome, ImportError> {
+		let (retracted, enacted) = self.route(self.best, target);
+		for hash in &retracted {
+			self.revert(hash);
+		}
+		for (i, hash) in enacted.iter().enumerate() {
+			if let Err(error) = self.execute(*hash) {
+				// Go back to the previous best chain, which was valid.
+				for hash in enacted[..i].iter().rev() {
+					self.revert(hash);
+				}
+				for hash in retracted.iter().rev() {
+					self.execute(*hash).expect("the blocks of the best chain were executed before");
+				}
+				self.remove_with_descendants(*hash);
+				return Err(ImportError::InvalidBlock { hash: *hash, error });
+			}
+		}
+		self.best = target;
+		Ok(ImportOutcome::Reorg { retracted, enacted })
+	}
+
+	/// The blocks to revert to go from the chain ending with `from` to the chain ending with `to`,
+	/// from the newest one, and the blocks to execute then, from the oldest one.
+	fn route(&self, mut from: types::Hash, mut to: types::Hash) -> (Vec<types::Hash>, Vec<types::Hash>) {
+		let (mut retracted, mut enacted) = (Vec::new(), Vec::new());
+		// Walk back from the higher of the two blocks until both meet at their common ancestor. Every
+		// block descends from the root, so they meet at the root at the latest.
+		while from != to {
+			let number = |hash| self.chain_info(hash).expect("blocks of the route are in the tree").number;
+			if number(&from) >= number(&to) {
+				retracted.push(from);
+				from = self.blocks[&from].block.header.parent_hash;
+			} else {
+				enacted.push(to);
+				to = self.blocks[&to].block.header.parent_hash;
+			}
+		}
+		enacted.reverse();
+		(retracted, enacted)
+	}
+
+	/// Execute the block `hash` on top of the current state, keeping its undo log if it is valid.
+	/// An invalid block does not change the state, see `Runtime::execute_block`.
+	fn execute(&mut self, hash: types::Hash) -> Result<(), DispatchError> {
+		let block = self.blocks[&hash].block.clone();
+		let storage = self.runtime.system.storage().clone();
+		storage.start_undo_log();
+		// The receipt of the block is kept by the system pallet.
+		let result = self.runtime.execute_block(block).map(|_| ());
+		let undo_log = storage.take_undo_log();
+		if result.is_ok() {
+			storage.insert_aux(aux_key(b"undo", &hash), undo_log.encode());
+			self.blocks.get_mut(&hash).expect("executed blocks are in the tree").undo_log = Some(undo_log);
+		}
+		result
+	}
+
+	/// Revert the state changes of the block `hash`, which must be the last executed block, and
+	/// forget its receipt.
+	fn revert(&mut self, hash: &types::Hash) {
+		let block = self.blocks.get_mut(hash).expect("reverted blocks are in the tree");
+		let undo_log = block.undo_log.take().expect("the blocks of the best chain have an undo log");
+		let number = block.block.header.block_number;
+		let storage = self.runtime.system.storage();
+		storage.remove_aux(&aux_key(b"undo", hash));
+		storage.undo(undo_log);
+		self.runtime.system.forget_receipt(number);
+	}
+
+	/// Forget the blocks which became final, and the forks which don't include them. The last final
+	/// block becomes the root of the tree.
+	fn prune(&mut self) {
+		let best = self.best_chain_info();
+		let Some(final_number) = best.number.checked_sub(FINALITY_DEPTH) else {
+			return;
+		};
+		if final_number <= self.root_chain.number {
+			return;
+		}
+		let mut root = self.best;
+		while self.blocks[&root].chain.number > final_number {
+			root = self.blocks[&root].block.header.parent_hash;
+		}
+		let pruned = self
+			.blocks
+			.keys()
+			.filter(|hash| !self.is_descendant(hash, &root))
+			.copied()
+			.collect::<Vec<_>>();
+		self.root_chain = self.blocks[&root].chain;
+		self.root = root;
+		for hash in pruned {
+			self.remove(&hash);
+		}
+	}
+
+	/// Whether the block `hash` is a descendant of the block `ancestor`, not counting `ancestor`.
+	fn is_descendant(&self, hash: &types::Hash, ancestor: &types::Hash) -> bool {
+		let number = self.chain_info(ancestor).expect("ancestors are in the tree").number;
+		let mut hash = *hash;
+		while let Some(block) = self.blocks.get(&hash).filter(|block| block.chain.number > number) {
+			hash = block.block.header.parent_hash;
+			if hash == *ancestor {
+				return true;
+			}
+		}
+		false
+	}
+
+	fn insert(&mut self, hash: types::Hash, block: TreeBlock) {
+		let storage = self.runtime.system.storage();
+		storage.insert_aux(aux_key(b"block", &hash), (&block.block, block.chain).encode());
+		self.blocks.insert(hash, block);
+	}
+
+	fn remove(&mut self, hash: &types::Hash) {
+		let storage = self.runtime.system.storage();
+		storage.remove_aux(&aux_key(b"block", hash));
+		storage.remove_aux(&aux_key(b"undo", hash));
+		self.blocks.remove(hash);
+	}
+
+	/// Write the root, the best block and the list of blocks of the tree to the storage. The blocks
+	/// themselves are written as they are inserted and executed.
+	fn store(&self) {
+		let stored = StoredTree {
+			root: self.root,
+			root_chain: self.root_chain,
+			best: self.best,
+			blocks: self.blocks.keys().copied().collect(),
+		};
+		self.runtime.system.storage().insert_aux(TREE_KEY.to_vec(), stored.encode());
+	}
+
+	fn remove_with_descendants(&mut self, hash: types::Hash) {
+		let mut removed = vec![hash];
+		while let Some(hash) = removed.pop() {
+			self.remove(&hash);
+			removed.extend(
+				self.blocks
+					.iter()
+					.filter(|(_, block)| block.block.header.parent_hash == hash)
+					.map(|(child, _)| *child),
+			);
+		}
+	}
+}
+
+/// The key of a block of the tree, or of its undo log, in the auxiliary data of the storage.
+fn aux_key(kind: &[u8], hash: &types::Hash) -> Vec<u8> {
+	[TREE_KEY, b":", kind, b":", &hash.0].concat()
+}
+
+impl<F: ForkChoice> std::fmt::Debug for BlockTree<F> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("BlockTree")
+			.field("root", &self.root)
+			.field("best", &self.best)
+			.field("blocks", &self.blocks.len())
+			.field("runtime", &self.runtime)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{BlockTree, HeaviestChain, ImportError, ImportOutcome, LongestChain, FINALITY_DEPTH};
+	use crate::{
+		balances,
+		block_builder::BlockBuilder,
+		chain_spec::ChainSpec,
+		support::crypto::{ed25519::Pair, Sha256, H256},
+		types, Runtime, RuntimeCall,
+	};
+
+	fn node() -> Runtime {
+		ChainSpec::development().genesis.build()
+	}
+
+	/// Build a block on top of `runtime` with a transfer of `amount` from alice to bob for each
+	/// amount, and import it into `runtime`.
+	fn block(runtime: &mut Runtime, amounts: &[u128]) -> types::Block {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob").public();
+		let nonce = runtime.system.account_nonce(&alice.public());
+		let mut builder = BlockBuilder::new(runtime, None);
+		for (i, amount) in amounts.iter().enumerate() {
+			let call = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: *amount });
+			assert_eq!(builder.push(crate::sign(&alice, nonce + i as u32, call)), Ok(Ok(())));
+		}
+		let block = builder.build();
+		runtime.execute_block(block.clone()).unwrap();
+		block
+	}
+
+	fn hash_of(block: &types::Block) -> H256 {
+		block.header.hash::<Sha256>()
+	}
+
+	#[test]
+	fn reorg_to_the_longest_chain() {
+		let bob = Pair::from_name("bob").public();
+		let mut tree = BlockTree::new(node(), LongestChain);
+		let (mut fork_a, mut fork_b) = (node(), node());
+		let a1 = block(&mut fork_a, &[10]);
+		let b1 = block(&mut fork_b, &[20]);
+		let b2 = block(&mut fork_b, &[30]);
+
+		assert_eq!(tree.import(a1.clone()), Ok(ImportOutcome::Best));
+		assert_eq!(tree.import(b1.clone()), Ok(ImportOutcome::Fork));
+		assert_eq!(tree.import(b1.clone()), Err(ImportError::AlreadyImported));
+		assert_eq!(tree.runtime().balances.balance(&bob), 10);
+
+		assert_eq!(
+			tree.import(b2.clone()),
+			Ok(ImportOutcome::Reorg { retracted: vec![hash_of(&a1)], enacted: vec![hash_of(&b1), hash_of(&b2)] })
+		);
+		assert_eq!(tree.best_hash(), hash_of(&b2));
+		assert_eq!(tree.runtime().state_root(), fork_b.state_root());
+		assert_eq!(tree.runtime().balances.balance(&bob), 50);
+		assert_eq!(tree.runtime().system.block_number(), 2);
+
+		// A block on top of the retracted fork needs its parent to be executed again.
+		let a2 = block(&mut fork_a, &[]);
+		let a3 = block(&mut fork_a, &[]);
+		assert_eq!(tree.import(a2.clone()), Ok(ImportOutcome::Fork));
+		assert_eq!(
+			tree.import(a3.clone()),
+			Ok(ImportOutcome::Reorg {
+				retracted: vec![hash_of(&b2), hash_of(&b1)],
+				enacted: vec![hash_of(&a1), hash_of(&a2), hash_of(&a3)]
+			})
+		);
+		assert_eq!(tree.runtime().state_root(), fork_a.state_root());
+		assert_eq!(tree.runtime().system.parent_hash(), hash_of(&a3));
+	}
+
+	#[test]
+	fn reorg_to_the_heaviest_chain() {
+		let mut tree = BlockTree::new(node(), HeaviestChain);
+		let (mut fork_a, mut fork_b) = (node(), node());
+		let a1 = block(&mut fork_a, &[]);
+		let a2 = block(&mut fork_a, &[]);
+		let b1 = block(&mut fork_b, &[10]);
+
+		assert_eq!(tree.import(a1.clone()), Ok(ImportOutcome::Best));
+		assert_eq!(tree.import(a2.clone()), Ok(ImportOutcome::Best));
+		// The fork is shorter, but its transfer makes it heavier.
+		assert_eq!(
+			tree.import(b1.clone()),
+			Ok(ImportOutcome::Reorg { retracted: vec![hash_of(&a2), hash_of(&a1)], enacted: vec![hash_of(&b1)] })
+		);
+		assert_eq!(tree.runtime().state_root(), fork_b.state_root());
+		// The receipts are those of the best chain, and the retracted block 2 has none.
+		assert_eq!(tree.runtime().system.block_receipt(1), fork_b.system.block_receipt(1));
+		assert_eq!(tree.runtime().system.block_receipt(2), None);
+	}
+
+	#[test]
+	fn invalid_fork_is_removed() {
+		let mut tree = BlockTree::new(node(), LongestChain);
+		let (mut fork_a, mut fork_b) = (node(), node());
+		let a1 = block(&mut fork_a, &[10]);
+		let mut b1 = block(&mut fork_b, &[20]);
+		b1.header.state_root = H256::default();
+		let mut b2 = block(&mut fork_b, &[30]);
+		b2.header.parent_hash = hash_of(&b1);
+
+		assert_eq!(tree.import(a1.clone()), Ok(ImportOutcome::Best));
+		let root = tree.runtime().state_root();
+		assert_eq!(tree.import(b1.clone()), Ok(ImportOutcome::Fork));
+		assert!(matches!(tree.import(b2.clone()), Err(ImportError::InvalidBlock { hash, .. }) if hash == hash_of(&b1)));
+
+		// The best chain is back, and the invalid fork is gone.
+		assert_eq!(tree.best_hash(), hash_of(&a1));
+		assert_eq!(tree.runtime().state_root(), root);
+		assert!(!tree.contains(&hash_of(&b1)) && !tree.contains(&hash_of(&b2)));
+		assert_eq!(tree.import(b2), Err(ImportError::UnknownParent));
+	}
+
+	#[test]
+	fn final_blocks_are_pruned() {
+		let mut tree = BlockTree::new(node(), LongestChain);
+		let (mut fork_a, mut fork_b, mut fork_c) = (node(), node(), node());
+		let b1 = block(&mut fork_b, &[20]);
+		let b2 = block(&mut fork_b, &[]);
+		let a1 = block(&mut fork_a, &[10]);
+		assert_eq!(tree.import(a1.clone()), Ok(ImportOutcome::Best));
+		assert_eq!(tree.import(b1.clone()), Ok(ImportOutcome::Fork));
+		for _ in 0..FINALITY_DEPTH {
+			assert_eq!(tree.import(block(&mut fork_a, &[])), Ok(ImportOutcome::Best));
+		}
+
+		// Block 1 is final, so it is the root of the tree, and the fork of b1 is gone.
+		assert_eq!(tree.runtime().system.block_number(), FINALITY_DEPTH + 1);
+		assert_eq!(tree.root, hash_of(&a1));
+		assert_eq!(tree.blocks.len(), FINALITY_DEPTH as usize);
+		assert!(tree.blocks.values().all(|block| block.undo_log.is_some()));
+		assert!(!tree.contains(&hash_of(&b1)));
+		assert_eq!(tree.import(b2), Err(ImportError::UnknownParent));
+		assert_eq!(tree.import(block(&mut fork_c, &[])), Err(ImportError::UnknownParent));
+	}
+}
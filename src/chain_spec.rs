@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use crate::{balances, proof_of_existence, sudo, support::crypto::ed25519, RuntimeGenesisConfig};
+
+/// The description of a chain: its name, and the initial state of its runtime.
+///
+/// Chain specs are stored as JSON files, where accounts are hex encoded public keys.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+	pub name: String,
+	pub genesis: RuntimeGenesisConfig,
+}
+
+/// The reasons a chain spec can't be loaded.
+#[derive(Debug)]
+pub enum ChainSpecError {
+	/// The file could not be read.
+	Io(std::io::Error),
+	/// The file is not a valid JSON chain spec.
+	Json(serde_json::Error),
+}
+
+impl From<std::io::Error> for ChainSpecError {
+	fn from(error: std::io::Error) -> Self {
+		ChainSpecError::Io(error)
+	}
+}
+
+impl From<serde_json::Error> for ChainSpecError {
+	fn from(error: serde_json::Error) -> Self {
+		ChainSpecError::Json(error)
+	}
+}
+
+impl std::fmt::Display for ChainSpecError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			ChainSpecError::Io(error) => write!(f, "Failed to read the chain spec: {}", error),
+			ChainSpecError::Json(error) => write!(f, "Invalid chain spec: {}", error),
+		}
+	}
+}
+
+impl ChainSpec {
+	/// A chain for development, where alice is endowed, owns a claim and holds the sudo key. The keys
+	/// of the accounts are derived with `ed25519::Pair::from_name`.
+	pub fn development() -> Self {
+		let alice = ed25519::Pair::from_name("alice").public();
+		Self {
+			name: "Development".to_string(),
+			genesis: RuntimeGenesisConfig {
+				balances: balances::GenesisConfig { balances: vec![(alice, 100_000)] },
+				proof_of_existence: proof_of_existence::GenesisConfig { claims: vec![("genesis".to_string(), alice)] },
+				sudo: sudo::GenesisConfig { key: Some(alice) },
+				..Default::default()
+			},
+		}
+	}
+
+	/// Load a chain spec from a JSON file.
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, ChainSpecError> {
+		Self::from_json(&std::fs::read_to_string(path)?)
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, ChainSpecError> {
+		Ok(serde_json::from_str(json)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ChainSpec, ChainSpecError};
+	use crate::support::crypto::ed25519::Pair;
+
+	#[test]
+	fn json_chain_spec() {
+		let json = serde_json::to_string(&ChainSpec::development()).unwrap();
+		let spec = ChainSpec::from_json(&json).unwrap();
+		assert_eq!(spec.name, "Development");
+
+		let alice = Pair::from_name("alice").public();
+		let runtime = spec.genesis.build();
+		assert_eq!(runtime.system.block_number(), 0);
+		assert_eq!(runtime.balances.balance(&alice), 100_000);
+		assert_eq!(runtime.proof_of_existence.get_claim(&"genesis".to_string()), Some(alice));
+		assert_eq!(runtime.sudo.key(), Some(alice));
+	}
+
+	#[test]
+	fn missing_pallets_use_their_default_genesis() {
+		let alice = Pair::from_name("alice").public();
+		let json = format!(
+			r#"{{ "name": "Test", "genesis": {{ "system": {{ "block_number": 7 }}, "balances": {{ "balances": [["{:?}", 5]] }} }} }}"#,
+			alice
+		);
+		let runtime = ChainSpec::from_json(&json).unwrap().genesis.build();
+		assert_eq!(runtime.system.block_number(), 7);
+		assert_eq!(runtime.balances.balance(&alice), 5);
+		assert_eq!(runtime.proof_of_existence.get_claim(&"genesis".to_string()), None);
+
+		let unknown_pallet = r#"{ "name": "Test", "genesis": { "staking": {} } }"#;
+		assert!(matches!(ChainSpec::from_json(unknown_pallet), Err(ChainSpecError::Json(_))));
+	}
+}
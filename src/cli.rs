@@ -0,0 +1,488 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{
+	block_tree::{BlockTree, ImportError, ImportOutcome, LongestChain},
+	chain_spec::{ChainSpec, ChainSpecError},
+	support::{codec, crypto::ed25519::Pair, storage::disk::DiskBackend, Decode, ExtrinsicReceipt, Storage},
+	system, types, Runtime, RuntimeEvent,
+};
+
+/// The help of `rsm`, which is also shown when the arguments are invalid.
+pub const USAGE: &str = "\
+Usage: rsm [--db <path>] [--chain <path>] <command>
+
+Commands:
+  import-blocks <file>     Import the encoded blocks in <file>, keeping the longest chain if they fork
+  query balance <account>  Print the balance of <account>
+  query claim <content>    Print the owner of the claim on <content>, or `none`
+  query receipt <block> [<index>]
+                           Print the receipt of a recent <block>, or of its extrinsic at <index>
+  export-state             Print the block number, state root and storage of the database as JSON
+  build-spec               Print the chain spec as JSON
+  metadata                 Print the metadata of the runtime as JSON
+  demo [<file>]            Run the demo on a new development chain, and write its blocks to <file>
+
+Options:
+  --db <path>     The chain database, created from the chain spec by import-blocks [default: rsm.db]
+  --chain <path>  A JSON chain spec, see `build-spec` [default: the development chain]
+  -h, --help      Print this help
+
+Accounts are 0x prefixed hex public keys, or the names of development accounts, e.g. alice.";
+
+/// The arguments of `rsm`.
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+	/// The path of the chain database, see `DiskBackend`.
+	pub db: PathBuf,
+	/// The path of the chain spec, or `None` for the development chain. It is only used when the
+	/// database is created, and by `build-spec`.
+	pub chain: Option<PathBuf>,
+	pub command: Command,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+	/// Import the blocks in a file, one encoded block after the other. The blocks can be on forks of
+	/// the chain, in which case the longest chain is kept, see `BlockTree`. The forks are kept in the
+	/// database until they are final, so the blocks of a later import can build on them.
+	ImportBlocks(PathBuf),
+	QueryBalance(types::AccountId),
+	QueryClaim(types::Content),
+	/// Print the receipt of a recent block, or of one of its extrinsics, see
+	/// `system::Pallet::block_receipt`.
+	QueryReceipt(types::BlockNumber, Option<usize>),
+	ExportState,
+	BuildSpec,
+	Metadata,
+	/// Run the demo in `main`, writing its blocks to the file if any, so they can be imported. The
+	/// demo always runs on the development chain.
+	Demo(Option<PathBuf>),
+	Help,
+}
+
+/// The reasons a command can fail.
+#[derive(Debug)]
+pub enum Error {
+	/// The arguments are invalid.
+	Usage(String),
+	Io(std::io::Error),
+	ChainSpec(ChainSpecError),
+	/// The file of blocks to import can't be decoded.
+	InvalidBlocks(codec::Error),
+	/// The database does not exist. It is only created by `import-blocks`.
+	NoDatabase(PathBuf),
+	/// A block could not be imported. The blocks before it were imported.
+	InvalidBlock {
+		number: types::BlockNumber,
+		error: String,
+	},
+}
+
+impl From<std::io::Error> for Error {
+	fn from(error: std::io::Error) -> Self {
+		Error::Io(error)
+	}
+}
+
+impl From<ChainSpecError> for Error {
+	fn from(error: ChainSpecError) -> Self {
+		Error::ChainSpec(error)
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+			Error::Io(error) => write!(f, "{}", error),
+			Error::ChainSpec(error) => write!(f, "{}", error),
+			Error::NoDatabase(path) => write!(f, "No database at {}, import blocks to create it", path.display()),
+			Error::InvalidBlocks(error) => write!(f, "Invalid blocks file: {}", error.0),
+			Error::InvalidBlock { number, error } => write!(f, "Failed to import block #{}: {}", number, error),
+		}
+	}
+}
+
+impl Cli {
+	/// Parse the arguments of `rsm`, without the name of the binary.
+	pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+		let mut args = args.into_iter();
+		let mut db = PathBuf::from("rsm.db");
+		let mut chain = None;
+		let mut positional = Vec::new();
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"--db" => db = args.next().ok_or_else(|| usage("--db expects a path"))?.into(),
+				"--chain" => chain = Some(args.next().ok_or_else(|| usage("--chain expects a path"))?.into()),
+				"-h" | "--help" => return Ok(Self { db, chain, command: Command::Help }),
+				_ if arg.starts_with('-') => return Err(usage(&format!("Unknown option {}", arg))),
+				_ => positional.push(arg),
+			}
+		}
+
+		let positional = positional.iter().map(String::as_str).collect::<Vec<_>>();
+		let command = match positional.as_slice() {
+			["import-blocks", file] => Command::ImportBlocks(file.into()),
+			["query", "balance", account] => Command::QueryBalance(parse_account(account)?),
+			["query", "claim", content] => Command::QueryClaim(content.to_string()),
+			["query", "receipt", block] => Command::QueryReceipt(parse_number(block)?, None),
+			["query", "receipt", block, index] => {
+				Command::QueryReceipt(parse_number(block)?, Some(parse_number(index)?))
+			},
+			["export-state"] => Command::ExportState,
+			["build-spec"] => Command::BuildSpec,
+			["metadata"] => Command::Metadata,
+			["demo"] => Command::Demo(None),
+			["demo", file] => Command::Demo(Some(file.into())),
+			[] => return Err(usage("Missing command")),
+			_ => return Err(usage(&format!("Invalid command `{}`", positional.join(" ")))),
+		};
+		if chain.is_some() && matches!(command, Command::Demo(_)) {
+			return Err(usage("The demo always runs on the development chain, it does not take --chain"));
+		}
+		Ok(Self { db, chain, command })
+	}
+
+	/// Run the command, writing its output to `out`.
+	pub fn run(&self, out: &mut impl Write) -> Result<(), Error> {
+		match &self.command {
+			Command::ImportBlocks(path) => {
+				let mut tree = BlockTree::new(self.open_or_create_runtime()?, LongestChain);
+				let bytes = std::fs::read(path)?;
+				let mut input = bytes.as_slice();
+				while !input.is_empty() {
+					let block = types::Block::decode(&mut input).map_err(Error::InvalidBlocks)?;
+					let number = block.header.block_number;
+					let hash = block.header.hash::<<Runtime as system::Config>::Hashing>();
+					// Importing the same file twice does not fail on the blocks imported the first time.
+					if tree.runtime().system.block_hash(number) == Some(hash) {
+						writeln!(out, "Block #{} ({:?}) is already imported", number, hash)?;
+						continue;
+					}
+					match tree.import(block) {
+						Ok(ImportOutcome::Best) => {
+							writeln!(out, "Imported block #{} ({:?})", number, hash)?;
+							write_failed_extrinsics(out, tree.runtime(), number)?;
+						},
+						Ok(ImportOutcome::Fork) => writeln!(out, "Imported block #{} ({:?}) on a fork", number, hash)?,
+						Ok(ImportOutcome::Reorg { retracted, enacted }) => {
+							writeln!(
+								out,
+								"Imported block #{} ({:?}), reverting {} blocks and executing {} blocks of its fork",
+								number,
+								hash,
+								retracted.len(),
+								enacted.len()
+							)?;
+							write_failed_extrinsics(out, tree.runtime(), number)?;
+						},
+						Err(ImportError::AlreadyImported) => {
+							writeln!(out, "Block #{} ({:?}) is already imported", number, hash)?
+						},
+						Err(error) => return Err(Error::InvalidBlock { number, error: error.to_string() }),
+					}
+					// The state always matches the best chain, whatever the outcome of the import.
+					tree.runtime().system.storage().flush()?;
+				}
+				let best_number = tree.runtime().system.block_number();
+				writeln!(out, "Best block: #{} ({:?})", best_number, tree.best_hash())?;
+			},
+			Command::QueryBalance(who) => {
+				writeln!(out, "{}", self.open_runtime()?.balances.balance(who))?;
+			},
+			Command::QueryClaim(content) => match self.open_runtime()?.proof_of_existence.get_claim(content) {
+				Some(owner) => writeln!(out, "{:?}", owner)?,
+				None => writeln!(out, "none")?,
+			},
+			Command::QueryReceipt(number, None) => match self.open_runtime()?.system.block_receipt(*number) {
+				Some(receipt) => {
+					for (index, extrinsic) in receipt.extrinsics.iter().enumerate() {
+						write_extrinsic_receipt(out, index, extrinsic)?;
+					}
+				},
+				None => writeln!(out, "none")?,
+			},
+			Command::QueryReceipt(number, Some(index)) => {
+				match self.open_runtime()?.system.extrinsic_receipt(*number, *index) {
+					Some(extrinsic) => write_extrinsic_receipt(out, *index, &extrinsic)?,
+					None => writeln!(out, "none")?,
+				}
+			},
+			Command::ExportState => {
+				let runtime = self.open_runtime()?;
+				let storage = runtime
+					.system
+					.storage()
+					.entries()
+					.into_iter()
+					.map(|(key, value)| (hex(&key), serde_json::Value::String(hex(&value))))
+					.collect::<serde_json::Map<_, _>>();
+				let state = serde_json::json!({
+					"block_number": runtime.system.block_number(),
+					"state_root": format!("{:?}", runtime.state_root()),
+					"storage": storage,
+				});
+				writeln!(out, "{}", serde_json::to_string_pretty(&state).expect("JSON values can be serialized"))?;
+			},
+			Command::BuildSpec => {
+				let json = serde_json::to_string_pretty(&self.chain_spec()?).expect("chain specs can be serialized");
+				writeln!(out, "{}", json)?;
+			},
+			Command::Metadata => {
+				let json = serde_json::to_string_pretty(&Runtime::metadata()).expect("metadata can be serialized");
+				writeln!(out, "{}", json)?;
+			},
+			Command::Demo(path) => {
+				let (runtime, blocks) = crate::demo();
+				match path {
+					Some(path) => {
+						std::fs::write(path, blocks)?;
+						writeln!(out, "Wrote the blocks of the demo to {}", path.display())?;
+					},
+					None => writeln!(out, "{:#?}", runtime)?,
+				}
+			},
+			Command::Help => writeln!(out, "{}", USAGE)?,
+		}
+		Ok(())
+	}
+
+	fn chain_spec(&self) -> Result<ChainSpec, Error> {
+		match &self.chain {
+			Some(path) => Ok(ChainSpec::load(path)?),
+			None => Ok(ChainSpec::development()),
+		}
+	}
+
+	/// Open the runtime on the existing database.
+	fn open_runtime(&self) -> Result<Runtime, Error> {
+		if !self.db.exists() {
+			return Err(Error::NoDatabase(self.db.clone()));
+		}
+		Ok(Runtime::with_storage(Storage::new(DiskBackend::open(&self.db)?)))
+	}
+
+	/// Open the runtime on the database, which is created from the chain spec if it is empty.
+	fn open_or_create_runtime(&self) -> Result<Runtime, Error> {
+		let storage = Storage::new(DiskBackend::open(&self.db)?);
+		if !storage.entries().is_empty() {
+			return Ok(Runtime::with_storage(storage));
+		}
+		let runtime = self.chain_spec()?.genesis.build_with_storage(storage);
+		runtime.system.storage().flush()?;
+		Ok(runtime)
+	}
+}
+
+/// Write the extrinsics of the last imported block `number` which failed, from its receipt.
+fn write_failed_extrinsics(out: &mut impl Write, runtime: &Runtime, number: types::BlockNumber) -> Result<(), Error> {
+	let receipt = runtime
+		.system
+		.block_receipt(number)
+		.expect("the last imported block has a receipt");
+	for (index, extrinsic) in receipt.extrinsics.iter().enumerate() {
+		match &extrinsic.result {
+			Ok(Ok(())) => {},
+			Ok(Err(error)) => writeln!(out, "  Extrinsic {} failed: {}", index, Runtime::describe_error(error))?,
+			Err(error) => writeln!(out, "  Extrinsic {} is invalid: {:?}", index, error)?,
+		}
+	}
+	Ok(())
+}
+
+/// Write the result, weight and events of the extrinsic at `index` in its block.
+fn write_extrinsic_receipt(
+	out: &mut impl Write,
+	index: usize,
+	extrinsic: &ExtrinsicReceipt<RuntimeEvent>,
+) -> Result<(), Error> {
+	match &extrinsic.result {
+		Ok(Ok(())) => writeln!(out, "Extrinsic {} succeeded, weight {}", index, extrinsic.weight)?,
+		Ok(Err(error)) => {
+			let error = Runtime::describe_error(error);
+			writeln!(out, "Extrinsic {} failed: {}, weight {}", index, error, extrinsic.weight)?
+		},
+		Err(error) => writeln!(out, "Extrinsic {} is invalid: {:?}", index, error)?,
+	}
+	for event in &extrinsic.events {
+		writeln!(out, "  {:?}", event)?;
+	}
+	Ok(())
+}
+
+fn usage(message: &str) -> Error {
+	Error::Usage(message.to_string())
+}
+
+fn parse_account(account: &str) -> Result<types::AccountId, Error> {
+	if account.starts_with("0x") {
+		account
+			.parse()
+			.map_err(|error: &str| usage(&format!("Invalid account {}: {}", account, error)))
+	} else {
+		Ok(Pair::from_name(account).public())
+	}
+}
+
+fn parse_number<N: std::str::FromStr>(number: &str) -> Result<N, Error> {
+	number.parse().map_err(|_| usage(&format!("Invalid number {}", number)))
+}
+
+/// Format `bytes` as a `0x` prefixed hex string.
+fn hex(bytes: &[u8]) -> String {
+	std::iter::once("0x".to_string())
+		.chain(bytes.iter().map(|byte| format!("{:02x}", byte)))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::{Cli, Command, Error};
+	use crate::{
+		balances,
+		block_builder::BlockBuilder,
+		chain_spec::ChainSpec,
+		support::{crypto::ed25519::Pair, Encode},
+		RuntimeCall,
+	};
+
+	fn parse(args: &str) -> Result<Cli, Error> {
+		Cli::parse(args.split_whitespace().map(String::from))
+	}
+
+	/// Run `rsm` with `args`, returning its output.
+	fn run(args: &str) -> Result<String, Error> {
+		let mut out = Vec::new();
+		parse(args)?.run(&mut out)?;
+		Ok(String::from_utf8(out).unwrap())
+	}
+
+	/// A path in the temporary directory which is unique to this test run.
+	fn temp_path(name: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("rsm-cli-{}-{}", std::process::id(), name));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn parse_arguments() {
+		let alice = Pair::from_name("alice").public();
+		let cli = parse("--db chain.db query balance alice").unwrap();
+		assert_eq!(cli, Cli { db: "chain.db".into(), chain: None, command: Command::QueryBalance(alice) });
+		let cli = parse(&format!("query balance {:?} --chain spec.json", alice)).unwrap();
+		assert_eq!(
+			cli,
+			Cli { db: "rsm.db".into(), chain: Some("spec.json".into()), command: Command::QueryBalance(alice) }
+		);
+		assert_eq!(parse("query receipt 2 1").unwrap().command, Command::QueryReceipt(2, Some(1)));
+		assert_eq!(parse("demo").unwrap().command, Command::Demo(None));
+		assert_eq!(parse("build-spec --help").unwrap().command, Command::Help);
+
+		assert!(matches!(parse(""), Err(Error::Usage(_))));
+		assert!(matches!(parse("query balance 0x1234"), Err(Error::Usage(_))));
+		assert!(matches!(parse("query nonce alice"), Err(Error::Usage(_))));
+		assert!(matches!(parse("query receipt -1"), Err(Error::Usage(_))));
+		assert!(matches!(parse("export-state --db"), Err(Error::Usage(_))));
+		assert!(matches!(parse("export-state --verbose"), Err(Error::Usage(_))));
+		assert!(matches!(parse("demo --chain spec.json"), Err(Error::Usage(_))));
+	}
+
+	#[test]
+	fn import_blocks_into_the_database() {
+		let (db, blocks) = (temp_path("db"), temp_path("blocks"));
+		let db_arg = format!("--db {}", db.display());
+		run(&format!("demo {}", blocks.display())).unwrap();
+
+		// Queries don't create the database.
+		assert!(matches!(run(&format!("{} query balance bob", db_arg)), Err(Error::NoDatabase(_))));
+		assert!(matches!(run(&format!("{} export-state", db_arg)), Err(Error::NoDatabase(_))));
+		assert!(!db.exists());
+		let output = run(&format!("{} import-blocks {}", db_arg, blocks.display())).unwrap();
+		assert_eq!(output.lines().filter(|line| line.starts_with("Imported block")).count(), 3);
+		// Charlie's claim in the second block fails, but the block is still imported.
+		assert!(output.contains("Extrinsic 1 failed: proof_of_existence::ClaimAlreadyExists"));
+		// The blocks are already imported.
+		let output = run(&format!("{} import-blocks {}", db_arg, blocks.display())).unwrap();
+		assert_eq!(output.lines().filter(|line| line.ends_with("is already imported")).count(), 3);
+
+		assert_eq!(run(&format!("{} query balance bob", db_arg)).unwrap(), "50000\n");
+		let bob = Pair::from_name("bob").public();
+		assert_eq!(run(&format!("{} query claim content", db_arg)).unwrap(), format!("{:?}\n", bob));
+		assert_eq!(run(&format!("{} query claim unknown", db_arg)).unwrap(), "none\n");
+		// The receipts are stored next to the state, and describe the failure of charlie's claim.
+		let output = run(&format!("{} query receipt 2", db_arg)).unwrap();
+		assert!(output.starts_with("Extrinsic 0 succeeded"));
+		assert!(output.contains("ClaimCreated"));
+		let output = run(&format!("{} query receipt 2 1", db_arg)).unwrap();
+		assert!(output.starts_with("Extrinsic 1 failed: proof_of_existence::ClaimAlreadyExists, weight"));
+		assert_eq!(run(&format!("{} query receipt 4", db_arg)).unwrap(), "none\n");
+		let state: serde_json::Value =
+			serde_json::from_str(&run(&format!("{} export-state", db_arg)).unwrap()).unwrap();
+		assert_eq!(state["block_number"], 3);
+
+		// The blocks before an incomplete block are still imported, here they already are.
+		let bytes = std::fs::read(&blocks).unwrap();
+		std::fs::write(&blocks, &bytes[..bytes.len() - 1]).unwrap();
+		let result = run(&format!("{} import-blocks {}", db_arg, blocks.display()));
+		assert!(matches!(result, Err(Error::InvalidBlocks(_))));
+
+		std::fs::remove_file(db).unwrap();
+		std::fs::remove_file(blocks).unwrap();
+	}
+
+	/// The encoded blocks of a fork of the development chain, which transfers `amount` from alice to
+	/// bob in each of its `len` blocks.
+	fn fork(amount: u128, len: u32) -> Vec<Vec<u8>> {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob").public();
+		let mut runtime = ChainSpec::development().genesis.build();
+		let mut blocks = Vec::new();
+		for nonce in 0..len {
+			let mut builder = BlockBuilder::new(&mut runtime, None);
+			let call = RuntimeCall::balances(balances::Call::transfer { to: bob, amount });
+			assert_eq!(builder.push(crate::sign(&alice, nonce, call)), Ok(Ok(())));
+			let block = builder.build();
+			blocks.push(block.encode());
+			runtime.execute_block(block).unwrap();
+		}
+		blocks
+	}
+
+	#[test]
+	fn import_blocks_of_forks() {
+		let (db, blocks) = (temp_path("fork-db"), temp_path("fork-blocks"));
+		std::fs::write(&blocks, [fork(10, 1), fork(20, 2)].concat().concat()).unwrap();
+
+		let db_arg = format!("--db {}", db.display());
+		let output = run(&format!("{} import-blocks {}", db_arg, blocks.display())).unwrap();
+		assert!(output.contains("on a fork"));
+		assert!(output.contains("reverting 1 blocks and executing 2 blocks of its fork"));
+		// The database holds the state of the longest chain.
+		assert_eq!(run(&format!("{} query balance bob", db_arg)).unwrap(), "40\n");
+
+		std::fs::remove_file(db).unwrap();
+		std::fs::remove_file(blocks).unwrap();
+	}
+
+	#[test]
+	fn reorg_across_imports() {
+		let (db, blocks) = (temp_path("reorg-db"), temp_path("reorg-blocks"));
+		let db_arg = format!("--db {}", db.display());
+		let (fork_a, fork_b) = (fork(10, 1), fork(20, 2));
+		std::fs::write(&blocks, [fork_a[0].clone(), fork_b[0].clone()].concat()).unwrap();
+		let output = run(&format!("{} import-blocks {}", db_arg, blocks.display())).unwrap();
+		assert!(output.contains("on a fork"));
+		assert_eq!(run(&format!("{} query balance bob", db_arg)).unwrap(), "10\n");
+
+		// The fork imported by the first run is known to the second one, which reorganises onto it.
+		std::fs::write(&blocks, &fork_b[1]).unwrap();
+		let output = run(&format!("{} import-blocks {}", db_arg, blocks.display())).unwrap();
+		assert!(output.contains("reverting 1 blocks and executing 2 blocks of its fork"));
+		assert_eq!(run(&format!("{} query balance bob", db_arg)).unwrap(), "40\n");
+
+		std::fs::remove_file(db).unwrap();
+		std::fs::remove_file(blocks).unwrap();
+	}
+}
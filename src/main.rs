@@ -1,89 +1,537 @@
 mod balances;
+mod block_builder;
+mod block_tree;
+mod chain_spec;
+mod cli;
 mod proof_of_existence;
+mod sudo;
 mod support;
 mod system;
+mod transaction_payment;
+mod transaction_pool;
 
-use crate::support::Dispatch;
+use crate::support::{crypto::ed25519, Dispatch, Encode};
 
 mod types {
-	pub type AccountId = String;
+	pub type AccountId = crate::support::crypto::ed25519::Public;
+	pub type Signature = crate::support::crypto::ed25519::Signature;
 	pub type Balance = u128;
 	pub type BlockNumber = u32;
 	pub type Nonce = u32;
-	pub type Extrinsic = crate::support::Extrinsic<AccountId, crate::RuntimeCall>;
-	pub type Header = crate::support::Header<BlockNumber>;
+	pub type Hash = crate::support::crypto::H256;
+	pub type Extrinsic = crate::support::Extrinsic<AccountId, Nonce, crate::RuntimeCall, Signature>;
+	pub type Header = crate::support::Header<BlockNumber, Hash, AccountId>;
 	pub type Block = crate::support::Block<Header, Extrinsic>;
-	pub type Content = &'static str;
+	pub type Content = String;
 }
 
 #[derive(Debug)]
-#[macros::runtime]
+#[macros::runtime(
+	failed_extrinsics = Include,
+	transaction_payment = transaction_payment,
+	currency = balances,
+	sudo = sudo
+)]
 pub struct Runtime {
 	system: system::Pallet<Self>,
 	balances: balances::Pallet<Self>,
+	transaction_payment: transaction_payment::Pallet<Self>,
 	proof_of_existence: proof_of_existence::Pallet<Self>,
+	sudo: sudo::Pallet<Self>,
 }
 
 impl system::Config for Runtime {
 	type AccountId = types::AccountId;
+	type Signature = types::Signature;
 	type Nonce = types::Nonce;
 	type BlockNumber = types::BlockNumber;
+	type Hash = types::Hash;
+	type Hashing = support::crypto::Sha256;
+	type RuntimeOrigin = RuntimeOrigin;
+	type RuntimeEvent = RuntimeEvent;
+
+	const BLOCK_HASH_COUNT: types::BlockNumber = 250;
+	const RECEIPT_COUNT: types::BlockNumber = 1_000;
+	const MAX_BLOCK_WEIGHT: support::Weight = 1_000_000;
+	const MAX_CHECKPOINTS: u32 = 16;
 }
 
 impl balances::Config for Runtime {
 	type Balance = types::Balance;
 }
 
+impl transaction_payment::Config for Runtime {
+	const TRANSACTION_BYTE_FEE: types::Balance = 1;
+	const WEIGHT_FEE: types::Balance = 1;
+	type FeeDestination = transaction_payment::ToBlockAuthor;
+}
+
 impl proof_of_existence::Config for Runtime {
 	type Content = types::Content;
 }
 
-fn main() {
-	let mut runtime = Runtime::new();
-	let alice = "alice".to_string();
-	let bob = "bob".to_string();
-	let charlie = "charlie".to_string();
-
-	// setup
-	runtime.balances.set_balance(&alice.clone(), 100);
-
-	let block_1 = types::Block {
-		header: support::Header { block_number: 1 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: alice.clone(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: bob.clone(), amount: 30 }),
-			},
-			support::Extrinsic {
-				caller: alice.clone(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: charlie.clone(), amount: 30 }),
+impl sudo::Config for Runtime {
+	type RuntimeCall = RuntimeCall;
+}
+
+impl transaction_pool::ValidateTransaction for Runtime {
+	type AccountId = types::AccountId;
+	type Nonce = types::Nonce;
+	type Call = RuntimeCall;
+	type Signature = types::Signature;
+
+	fn account_nonce(&self, who: &types::AccountId) -> types::Nonce {
+		self.system.account_nonce(who)
+	}
+
+	fn validate_transaction(&mut self, extrinsic: &types::Extrinsic) -> support::TransactionValidity {
+		Runtime::validate_transaction(self, extrinsic)
+	}
+}
+
+/// Sign `call` with `pair`, producing the extrinsic with the given `nonce`.
+fn sign(pair: &ed25519::Pair, nonce: types::Nonce, call: RuntimeCall) -> types::Extrinsic {
+	let signature = pair.sign(&types::Extrinsic::signing_payload(&call, &nonce));
+	support::Extrinsic { signer: pair.public(), signature, nonce, call }
+}
+
+/// The total fees paid by `who` in the current block, according to its events.
+fn fees_paid(runtime: &Runtime, who: &types::AccountId) -> types::Balance {
+	runtime
+		.system
+		.events()
+		.iter()
+		.filter_map(|record| match &record.event {
+			RuntimeEvent::transaction_payment(transaction_payment::Event::TransactionFeePaid { who: payer, fee })
+				if payer == who =>
+			{
+				Some(*fee)
 			},
-		],
-	};
+			_ => None,
+		})
+		.sum()
+}
+
+/// Run a demo of the runtime on a new development chain, see `rsm demo`.
+///
+/// Returns the runtime after the demo, and the blocks it imported, one encoded block after the
+/// other.
+fn demo() -> (Runtime, Vec<u8>) {
+	let mut runtime = chain_spec::ChainSpec::development().genesis.build();
+	let mut blocks = Vec::new();
+	let alice_pair = ed25519::Pair::from_name("alice");
+	let bob_pair = ed25519::Pair::from_name("bob");
+	let charlie_pair = ed25519::Pair::from_name("charlie");
+	let alice = alice_pair.public();
+	let bob = bob_pair.public();
+	let charlie = charlie_pair.public();
+	assert_eq!(runtime.balances.balance(&alice), 100_000);
+
+	// Alice can try her transfer to bob before signing it, which does not change the state.
+	let transfer_to_bob = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 30_000 });
+	let dry_run = runtime.dry_run(Some(alice), transfer_to_bob.clone());
+	assert_eq!(dry_run.result, Ok(()));
+	assert_eq!(
+		dry_run.events,
+		vec![RuntimeEvent::balances(balances::Event::Transfer { from: alice, to: bob, amount: 30_000 })]
+	);
+	assert_eq!(runtime.balances.balance(&alice), 100_000);
 
+	let mut pool = transaction_pool::TransactionPool::new();
+
+	// Alice's second transfer can't be included before her first one, so it waits in the pool.
+	let transfer_to_charlie = RuntimeCall::balances(balances::Call::transfer { to: charlie, amount: 30_000 });
+	pool.submit(&mut runtime, sign(&alice_pair, 1, transfer_to_charlie))
+		.expect("valid transaction");
+	assert_eq!((pool.ready_len(), pool.future_len()), (0, 1));
+	pool.submit(&mut runtime, sign(&alice_pair, 0, transfer_to_bob))
+		.expect("valid transaction");
+	assert_eq!((pool.ready_len(), pool.future_len()), (2, 0));
+
+	// Charlie authors the first block, so the fees paid by alice go to charlie.
+	let mut builder = block_builder::BlockBuilder::new(&mut runtime, Some(charlie));
+	let outcomes = builder.push_all(pool.ready().into_iter().cloned());
+	assert_eq!(outcomes, vec![Ok(Ok(())), Ok(Ok(()))]);
+	let block_1 = builder.build();
+
+	block_1.encode_to(&mut blocks);
 	runtime.execute_block(block_1).expect("invalid block");
+	assert_eq!(pool.maintain(&mut runtime), 2);
+	let alice_fees = fees_paid(&runtime, &alice);
+	assert!(alice_fees > 0);
 	assert_eq!(runtime.system.block_number(), 1);
-	assert_eq!(runtime.balances.balance(&alice), 40);
-	assert_eq!(runtime.balances.balance(&bob), 30);
-	assert_eq!(runtime.balances.balance(&charlie), 30);
-
-	let block_2 = types::Block {
-		header: support::Header { block_number: 2 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: bob.clone(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "content" }),
-			},
-			support::Extrinsic {
-				caller: charlie.clone(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "content" }),
-			},
-		],
-	};
+	assert_eq!(runtime.balances.balance(&alice), 40_000 - alice_fees);
+	assert_eq!(runtime.balances.balance(&bob), 30_000);
+	assert_eq!(runtime.balances.balance(&charlie), 30_000 + alice_fees);
+	assert_eq!(runtime.system.account_nonce(&alice), 2);
+
+	// Both claims pay the same fee, so bob's claim goes first, since it was submitted first.
+	let claim =
+		RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "content".to_string() });
+	pool.submit(&mut runtime, sign(&bob_pair, 0, claim.clone()))
+		.expect("valid transaction");
+	pool.submit(&mut runtime, sign(&charlie_pair, 0, claim))
+		.expect("valid transaction");
+
+	// The second block has no author, so its fees are burnt.
+	let mut builder = block_builder::BlockBuilder::new(&mut runtime, None);
+	let outcomes = builder.push_all(pool.ready().into_iter().cloned());
+	assert_eq!(outcomes[0], Ok(Ok(())));
+	let Ok(Err(error)) = outcomes[1] else { panic!("charlie's claim should fail") };
+	assert_eq!(Runtime::describe_error(&error), "proof_of_existence::ClaimAlreadyExists");
+	let block_2 = builder.build();
 
-	runtime.execute_block(block_2).expect("invalid block");
+	block_2.encode_to(&mut blocks);
+	let receipt = runtime.execute_block(block_2).expect("invalid block");
+	assert_eq!(receipt.extrinsics.len(), 2);
+	assert_eq!(runtime.system.extrinsic_receipt(2, 1), Some(receipt.extrinsics[1].clone()));
+	assert_eq!(receipt.extrinsics[1].result, Ok(Err(error)));
+	assert_eq!(pool.maintain(&mut runtime), 2);
 	assert_eq!(runtime.system.block_number(), 2);
-	assert_eq!(runtime.proof_of_existence.get_claim(&"content"), Some(&bob));
+	assert_eq!(runtime.proof_of_existence.get_claim(&"content".to_string()), Some(bob));
+	assert!(runtime.system.events().contains(&system::EventRecord {
+		phase: system::Phase::ApplyExtrinsic(0),
+		event: RuntimeEvent::proof_of_existence(proof_of_existence::Event::ClaimCreated {
+			owner: bob,
+			claim: "content".to_string()
+		}),
+	}));
+	// Charlie's claim failed, but charlie still paid for it.
+	assert_eq!(runtime.balances.balance(&charlie), 30_000 + alice_fees - fees_paid(&runtime, &charlie));
+	let bob_balance = runtime.balances.balance(&bob);
+	runtime.checkpoint();
+
+	// Alice holds the sudo key, so she can set bob's balance, which needs the root origin.
+	let force_set_balance = RuntimeCall::balances(balances::Call::force_set_balance { who: bob, amount: 50_000 });
+	let sudo = RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(force_set_balance) });
+	pool.submit(&mut runtime, sign(&alice_pair, 2, sudo))
+		.expect("valid transaction");
+
+	let mut builder = block_builder::BlockBuilder::new(&mut runtime, None);
+	let outcomes = builder.push_all(pool.ready().into_iter().cloned());
+	assert_eq!(outcomes, vec![Ok(Ok(()))]);
+	let block_3 = builder.build();
+
+	block_3.encode_to(&mut blocks);
+	runtime.execute_block(block_3.clone()).expect("invalid block");
+	assert_eq!(pool.maintain(&mut runtime), 1);
+	assert_eq!(runtime.balances.balance(&bob), 50_000);
+	assert!(runtime
+		.system
+		.events()
+		.iter()
+		.any(|record| record.event == RuntimeEvent::sudo(sudo::Event::Sudid { result: Ok(()) })));
+
+	// Going back to the checkpoint of the second block undoes the third one, which can then be
+	// imported again.
+	assert!(runtime.revert_to(2), "the checkpoint of block 2 is retained");
+	assert_eq!(runtime.system.checkpoints(), vec![2]);
+	assert_eq!(runtime.balances.balance(&bob), bob_balance);
+	runtime.execute_block(block_3).expect("invalid block");
+	assert_eq!(runtime.balances.balance(&bob), 50_000);
+
+	(runtime, blocks)
+}
+
+fn main() {
+	let result = cli::Cli::parse(std::env::args().skip(1)).and_then(|cli| cli.run(&mut std::io::stdout()));
+	if let Err(error) = result {
+		eprintln!("{}", error);
+		std::process::exit(1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		balances,
+		chain_spec::ChainSpec,
+		proof_of_existence, sudo,
+		support::{crypto::ed25519::Pair, Decode, Dispatch, DispatchError, Encode, ModuleError},
+		system, Runtime, RuntimeCall, RuntimeEvent,
+	};
+
+	#[test]
+	fn dry_run_does_not_change_the_state() {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&alice.public(), 100_000);
+		let root = runtime.state_root();
+
+		let transfer = |amount| RuntimeCall::balances(balances::Call::transfer { to: bob.public(), amount });
+		let dry_run = runtime.dry_run(Some(alice.public()), transfer(10));
+		assert_eq!(dry_run.result, Ok(()));
+		assert_eq!(
+			dry_run.events,
+			vec![RuntimeEvent::balances(balances::Event::Transfer {
+				from: alice.public(),
+				to: bob.public(),
+				amount: 10
+			})]
+		);
+		assert_eq!(dry_run.weight, 10_000);
+		// The balances of alice and bob are written.
+		assert_eq!(dry_run.storage_changes.len(), 2);
+		assert!(dry_run.storage_changes.iter().all(|(_, value)| value.is_some()));
+		assert_eq!(runtime.state_root(), root);
+		assert!(runtime.system.events().is_empty());
+
+		// A failing call makes no changes.
+		let dry_run = runtime.dry_run(Some(alice.public()), transfer(1_000_000));
+		assert!(dry_run.result.is_err());
+		assert!(dry_run.events.is_empty());
+		assert!(dry_run.storage_changes.is_empty());
+		assert_eq!(runtime.state_root(), root);
+	}
+
+	#[test]
+	fn dry_run_of_nested_calls() {
+		// Alice holds the sudo key.
+		let mut runtime = ChainSpec::development().genesis.build();
+		let alice = Pair::from_name("alice").public();
+		let bob = Pair::from_name("bob").public();
+		let root = runtime.state_root();
+		let sudo = |call| RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(call) });
+
+		// The events and writes of the call dispatched by sudo are part of the dry run.
+		let force_set_balance = RuntimeCall::balances(balances::Call::force_set_balance { who: bob, amount: 50 });
+		let dry_run = runtime.dry_run(Some(alice), sudo(force_set_balance));
+		assert_eq!(dry_run.result, Ok(()));
+		assert_eq!(
+			dry_run.events,
+			vec![
+				RuntimeEvent::balances(balances::Event::BalanceSet { who: bob, amount: 50 }),
+				RuntimeEvent::sudo(sudo::Event::Sudid { result: Ok(()) }),
+			]
+		);
+		assert_eq!(dry_run.storage_changes.len(), 1);
+		// ..but none of them are left behind.
+		assert_eq!(runtime.state_root(), root);
+		assert_eq!(runtime.balances.balance(&bob), 0);
+		assert!(runtime.system.events().is_empty());
+		assert!(runtime.sudo.take_call().is_none());
+
+		// The writes of a failing inner call are rolled back, even though sudo succeeds.
+		let transfer = RuntimeCall::balances(balances::Call::transfer { to: bob, amount: 10 });
+		let dry_run = runtime.dry_run(Some(alice), sudo(transfer));
+		assert_eq!(dry_run.result, Ok(()));
+		assert_eq!(
+			dry_run.events,
+			vec![RuntimeEvent::sudo(sudo::Event::Sudid { result: Err(DispatchError::BadOrigin) })]
+		);
+		assert!(dry_run.storage_changes.is_empty());
+		assert_eq!(runtime.state_root(), root);
+	}
+
+	#[test]
+	fn module_errors_name_their_pallet() {
+		let alice = Pair::from_name("alice").public();
+		let mut runtime = Runtime::new();
+		let revoke =
+			RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: "content".to_string() });
+		let error = runtime.dispatch(system::RawOrigin::Signed(alice), revoke).unwrap_err();
+		assert_eq!(
+			error,
+			DispatchError::Module(ModuleError {
+				pallet_index: Some(3),
+				error_index: 1,
+				message: Some("ClaimNotFound")
+			})
+		);
+		assert_eq!(Runtime::describe_error(&error), "proof_of_existence::ClaimNotFound");
+		// The error is named even without its message, e.g. when it is decoded from a receipt.
+		let decoded = DispatchError::decode_all(&error.encode()).unwrap();
+		assert_eq!(Runtime::describe_error(&decoded), "proof_of_existence::ClaimNotFound");
+
+		// An error keeps the pallet it was first attributed to.
+		assert_eq!(error.in_pallet(1), error);
+		let unattributed = DispatchError::from(balances::Error::InsufficientBalance);
+		assert_eq!(Runtime::describe_error(&unattributed), "InsufficientBalance (error 0)");
+		assert_eq!(Runtime::describe_error(&unattributed.in_pallet(1)), "balances::InsufficientBalance");
+		assert_eq!(Runtime::describe_error(&DispatchError::BadOrigin), "Bad origin");
+	}
+
+	#[test]
+	fn signed_extrinsics_cannot_make_root_calls() {
+		let alice = Pair::from_name("alice");
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&alice.public(), 100_000);
+
+		let force_set_balance =
+			RuntimeCall::balances(balances::Call::force_set_balance { who: alice.public(), amount: 1_000_000 });
+		let result = runtime.apply_extrinsic(crate::sign(&alice, 0, force_set_balance.clone()));
+		assert_eq!(result, Ok(Err(DispatchError::BadOrigin)));
+		// Neither can unsigned calls.
+		assert_eq!(runtime.dry_run(None, force_set_balance).result, Err(DispatchError::BadOrigin));
+		let transfer = RuntimeCall::balances(balances::Call::transfer { to: alice.public(), amount: 10 });
+		assert_eq!(runtime.dry_run(None, transfer).result, Err(DispatchError::BadOrigin));
+	}
+
+	/// A runtime whose pallets record when their hooks are called.
+	mod hooks {
+		use std::cell::RefCell;
+
+		use crate::{
+			support::{self, Dispatch},
+			system,
+		};
+
+		mod types {
+			pub type AccountId = crate::support::crypto::ed25519::Public;
+			pub type BlockNumber = u32;
+			pub type Extrinsic = crate::support::Extrinsic<AccountId, u32, super::RuntimeCall, super::Signature>;
+			pub type Header = crate::support::Header<BlockNumber, crate::support::crypto::H256, AccountId>;
+			pub type Block = crate::support::Block<Header, Extrinsic>;
+		}
+
+		type Signature = support::crypto::ed25519::Signature;
+
+		thread_local! {
+			/// The hooks called so far, in order, with the weight given to `on_idle`.
+			static CALLS: RefCell<Vec<(&'static str, &'static str, support::Weight)>> = const { RefCell::new(Vec::new()) };
+		}
+
+		fn record(pallet: &'static str, hook: &'static str, weight: support::Weight) {
+			CALLS.with(|calls| calls.borrow_mut().push((pallet, hook, weight)));
+		}
+
+		/// Defines a pallet named `$name` whose `on_initialize` and `on_idle` consume the given weights.
+		macro_rules! hooked_pallet {
+			($name:ident, $initialize_weight:expr, $idle_weight:expr) => {
+				mod $name {
+					use crate::support::{BuildGenesisConfig, Hooks, Storage, Transactional, Weight};
+
+					#[derive(serde::Serialize, serde::Deserialize)]
+					pub struct GenesisConfig<T: Config> {
+						#[serde(skip)]
+						_config: std::marker::PhantomData<T>,
+					}
+
+					impl<T: Config> Default for GenesisConfig<T> {
+						fn default() -> Self {
+							Self { _config: std::marker::PhantomData }
+						}
+					}
+
+					impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+						fn build(&self, _pallet: &mut Pallet<T>) {}
+					}
+
+					pub trait Config: crate::system::Config {}
+
+					pub struct Pallet<T: Config> {
+						_config: std::marker::PhantomData<T>,
+					}
+
+					#[macros::event]
+					pub enum Event<T: Config> {
+						Initialized { block_number: T::BlockNumber },
+					}
+
+					#[macros::error]
+					#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+					pub enum Error {}
+
+					impl<T: Config> Pallet<T> {
+						pub fn new(_storage: &Storage) -> Self {
+							Self { _config: std::marker::PhantomData }
+						}
+
+						pub fn take_events(&mut self) -> Vec<Event<T>> {
+							Vec::new()
+						}
+					}
+
+					#[macros::call]
+					impl<T: Config> Pallet<T> {}
+
+					impl<T: Config> Transactional for Pallet<T> {
+						fn start_transaction(&mut self) {}
+						fn commit_transaction(&mut self) {}
+						fn rollback_transaction(&mut self) {}
+					}
+
+					impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {
+						fn on_initialize(&mut self, _block_number: T::BlockNumber) -> Weight {
+							super::record(stringify!($name), "on_initialize", 0);
+							$initialize_weight
+						}
+
+						fn on_idle(&mut self, remaining_weight: Weight) -> Weight {
+							super::record(stringify!($name), "on_idle", remaining_weight);
+							$idle_weight
+						}
+
+						fn on_finalize(&mut self, _block_number: T::BlockNumber) {
+							super::record(stringify!($name), "on_finalize", 0);
+						}
+					}
+				}
+			};
+		}
+
+		hooked_pallet!(first, 10, 5);
+		hooked_pallet!(second, 20, 7);
+
+		#[macros::runtime(failed_extrinsics = Include)]
+		pub struct Runtime {
+			system: system::Pallet<Self>,
+			first: first::Pallet<Self>,
+			second: second::Pallet<Self>,
+		}
+
+		impl system::Config for Runtime {
+			type AccountId = types::AccountId;
+			type Signature = Signature;
+			type Nonce = u32;
+			type BlockNumber = types::BlockNumber;
+			type Hash = support::crypto::H256;
+			type Hashing = support::crypto::Sha256;
+			type RuntimeOrigin = RuntimeOrigin;
+			type RuntimeEvent = RuntimeEvent;
+
+			const BLOCK_HASH_COUNT: types::BlockNumber = 250;
+			const RECEIPT_COUNT: types::BlockNumber = 1_000;
+			const MAX_BLOCK_WEIGHT: support::Weight = 1_000;
+			const MAX_CHECKPOINTS: u32 = 16;
+		}
+
+		impl first::Config for Runtime {}
+		impl second::Config for Runtime {}
+
+		#[test]
+		fn hooks_are_called_in_declaration_order() {
+			let genesis = RuntimeGenesisConfig::default();
+			let mut builder = genesis.build();
+			let parent_hash = builder.system.parent_hash();
+			builder.initialize_block(None);
+			// The weight consumed by `on_initialize` is taken from the block.
+			assert_eq!(builder.system.block_weight(), 30);
+			builder.finalize_block();
+			assert_eq!(builder.system.block_weight(), 42);
+			let expected_calls = vec![
+				("first", "on_initialize", 0),
+				("second", "on_initialize", 0),
+				// Each `on_idle` is given what is left of the block after the previous one.
+				("first", "on_idle", 1_000 - 30),
+				("second", "on_idle", 1_000 - 35),
+				("first", "on_finalize", 0),
+				("second", "on_finalize", 0),
+			];
+			assert_eq!(CALLS.with(|calls| calls.take()), expected_calls);
 
-	println!("{:#?}", runtime);
+			// Another node executing the block calls the same hooks, and gets the same state.
+			let header = types::Header {
+				parent_hash,
+				block_number: 1,
+				state_root: builder.state_root(),
+				extrinsics_root: Runtime::extrinsics_root(&[]),
+				author: None,
+			};
+			let mut runtime = genesis.build();
+			runtime.execute_block(types::Block { header, extrinsics: Vec::new() }).unwrap();
+			assert_eq!(CALLS.with(|calls| calls.take()), expected_calls);
+		}
+	}
 }
@@ -1,42 +1,130 @@
-use std::{collections::BTreeMap, fmt::Debug};
+use std::fmt::Debug;
+
+use crate::support::{
+	storage::storage_item, BuildGenesisConfig, Decode, Encode, Hooks, Storage, StorageMap, Transactional,
+	TransactionalValue,
+};
+
+/// The initial state of the proof of existence pallet.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(
+	bound(
+		serialize = "T::AccountId: serde::Serialize, T::Content: serde::Serialize",
+		deserialize = "T::AccountId: serde::Deserialize<'de>, T::Content: serde::Deserialize<'de>"
+	),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The pre-existing claims, with their owner.
+	pub claims: Vec<(T::Content, T::AccountId)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { claims: Vec::new() }
+	}
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+	fn build(&self, pallet: &mut Pallet<T>) {
+		for (claim, owner) in &self.claims {
+			pallet.claims.insert(claim.clone(), owner.clone());
+		}
+	}
+}
 
 pub trait Config: crate::system::Config {
-	type Content: Debug + Ord;
+	type Content: Debug + Ord + Clone + Encode + Decode;
 }
 
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-	claims: BTreeMap<T::Content, T::AccountId>,
+	claims: StorageMap<T::Content, T::AccountId>,
+	/// Events deposited by this pallet, which are yet to be collected by the runtime.
+	events: TransactionalValue<Vec<Event<T>>>,
+}
+
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `owner` created a claim on `claim`.
+	ClaimCreated { owner: T::AccountId, claim: T::Content },
+	/// `owner` revoked their claim on `claim`.
+	ClaimRevoked { owner: T::AccountId, claim: T::Content },
+}
+
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The content has already been claimed.
+	ClaimAlreadyExists,
+	/// The content has not been claimed.
+	ClaimNotFound,
+	/// The claim is owned by another account.
+	NotClaimOwner,
 }
 
 impl<T: Config> Pallet<T> {
-	pub fn new() -> Self {
-		Self { claims: BTreeMap::new() }
+	pub fn new(storage: &Storage) -> Self {
+		Self {
+			claims: storage_item!(StorageMap<T::Content, T::AccountId>, storage, "ProofOfExistence", "Claims"),
+			events: TransactionalValue::new(Vec::new()),
+		}
+	}
+
+	pub fn deposit_event(&mut self, event: Event<T>) {
+		self.events.get_mut().push(event);
+	}
+
+	/// Take all the events deposited so far, see `system::Pallet::deposit_event`.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		std::mem::take(self.events.get_mut())
 	}
 
-	pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
+	pub fn get_claim(&self, claim: &T::Content) -> Option<T::AccountId> {
 		self.claims.get(claim)
 	}
 }
 
+impl<T: Config> Transactional for Pallet<T> {
+	fn start_transaction(&mut self) {
+		self.events.start_transaction();
+	}
+
+	fn commit_transaction(&mut self) {
+		self.events.commit_transaction();
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.events.rollback_transaction();
+	}
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
 #[macros::call]
 impl<T: Config> Pallet<T> {
+	#[call_index(0)]
+	#[weight(5_000)]
 	pub fn create_claim(&mut self, caller: T::AccountId, claim: T::Content) -> crate::support::DispatchResult {
-		if self.claims.get(&claim).is_some() {
-			return Err("Claim already exists");
+		if self.claims.contains_key(&claim) {
+			return Err(Error::ClaimAlreadyExists.into());
 		}
-		self.claims.insert(claim, caller);
+		self.claims.insert(claim.clone(), caller.clone());
+		self.deposit_event(Event::ClaimCreated { owner: caller, claim });
 		Ok(())
 	}
 
+	#[call_index(1)]
+	#[weight(5_000)]
 	pub fn revoke_claim(&mut self, caller: T::AccountId, claim: T::Content) -> crate::support::DispatchResult {
-		let owner = self.claims.get(&claim).ok_or("claim does not exist")?;
+		let owner = self.claims.get(&claim).ok_or(Error::ClaimNotFound)?;
 
-		if owner != &caller {
-			return Err("caller is not the owner");
+		if owner != caller {
+			return Err(Error::NotClaimOwner.into());
 		}
 
-		self.claims.remove(&claim);
+		self.claims.remove(claim.clone());
+		self.deposit_event(Event::ClaimRevoked { owner: caller, claim });
 
 		Ok(())
 	}
@@ -47,29 +135,70 @@ mod test {
 	struct TestConfig;
 
 	impl super::Config for TestConfig {
-		type Content = &'static str;
+		type Content = String;
 	}
 
 	impl crate::system::Config for TestConfig {
-		type AccountId = &'static str;
+		type AccountId = String;
+		type Signature = crate::support::crypto::TestSignature<String>;
 		type Nonce = u32;
 		type BlockNumber = u32;
+		type Hash = crate::support::crypto::H256;
+		type Hashing = crate::support::crypto::Sha256;
+		type RuntimeOrigin = crate::system::RawOrigin<Self::AccountId>;
+		type RuntimeEvent = ();
+
+		const BLOCK_HASH_COUNT: u32 = 250;
+		const RECEIPT_COUNT: u32 = 250;
+		const MAX_BLOCK_WEIGHT: u64 = 1_000_000;
+		const MAX_CHECKPOINTS: u32 = 0;
 	}
 
 	#[test]
 	fn basic_proof_of_existence() {
-		let mut proof_of_exixtence = super::Pallet::<TestConfig>::new();
+		let mut proof_of_exixtence = super::Pallet::<TestConfig>::new(&Default::default());
+		let alice = "alice".to_string();
+		let content = "content".to_string();
 		// intial check
-		assert_eq!(proof_of_exixtence.get_claim(&"none"), None);
+		assert_eq!(proof_of_exixtence.get_claim(&"none".to_string()), None);
 		// create claim
-		assert_eq!(proof_of_exixtence.create_claim("alice", "content"), Ok(()));
+		assert_eq!(proof_of_exixtence.create_claim(alice.clone(), content.clone()), Ok(()));
 		// get claim
-		assert_eq!(proof_of_exixtence.get_claim(&"content"), Some(&"alice"));
+		assert_eq!(proof_of_exixtence.get_claim(&content), Some(alice.clone()));
 		// already exists
-		assert_eq!(proof_of_exixtence.create_claim("bob", "content"), Err("Claim already exists"));
+		assert_eq!(
+			proof_of_exixtence.create_claim("bob".to_string(), content.clone()),
+			Err(super::Error::ClaimAlreadyExists.into())
+		);
 		// revoke
-		assert_eq!(proof_of_exixtence.revoke_claim("alice", "content"), Ok(()));
+		assert_eq!(proof_of_exixtence.revoke_claim(alice.clone(), content.clone()), Ok(()));
 		// invoke again
-		assert_eq!(proof_of_exixtence.revoke_claim("alice", "content"), Err("claim does not exist"));
+		assert_eq!(
+			proof_of_exixtence.revoke_claim(alice.clone(), content.clone()),
+			Err(super::Error::ClaimNotFound.into())
+		);
+		// events
+		assert_eq!(
+			proof_of_exixtence.take_events(),
+			vec![
+				super::Event::ClaimCreated { owner: alice.clone(), claim: content.clone() },
+				super::Event::ClaimRevoked { owner: alice, claim: content },
+			]
+		);
+	}
+
+	#[test]
+	fn genesis_config() {
+		let mut proof_of_existence = super::Pallet::<TestConfig>::new(&Default::default());
+		let alice = "alice".to_string();
+		let content = "content".to_string();
+		let genesis = super::GenesisConfig::<TestConfig> { claims: vec![(content.clone(), alice.clone())] };
+		crate::support::BuildGenesisConfig::build(&genesis, &mut proof_of_existence);
+
+		assert_eq!(proof_of_existence.get_claim(&content), Some(alice));
+		assert_eq!(
+			proof_of_existence.revoke_claim("bob".to_string(), content),
+			Err(super::Error::NotClaimOwner.into())
+		);
 	}
 }
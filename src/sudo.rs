@@ -0,0 +1,246 @@
+use crate::{
+	support::{
+		storage::storage_item, BuildGenesisConfig, DispatchResult, Encode, GetDispatchInfo, Hooks, Storage,
+		StorageValue, Transactional, TransactionalValue,
+	},
+	system::RawOrigin,
+};
+
+pub trait Config: crate::system::Config {
+	/// The aggregated call type of the runtime, which the sudo key can dispatch.
+	type RuntimeCall: Clone + Encode + GetDispatchInfo;
+}
+
+/// The initial state of the sudo pallet.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(
+	bound(serialize = "T::AccountId: serde::Serialize", deserialize = "T::AccountId: serde::Deserialize<'de>"),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The account holding the sudo key, if any.
+	pub key: Option<T::AccountId>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { key: None }
+	}
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+	fn build(&self, pallet: &mut Pallet<T>) {
+		match &self.key {
+			Some(key) => pallet.key.set(key.clone()),
+			None => pallet.key.kill(),
+		}
+	}
+}
+
+/// A pallet which lets a single account dispatch any call with root privileges.
+///
+/// A pallet can't dispatch a `RuntimeCall` by itself, so the calls of this pallet only check the
+/// sudo key and store the call to dispatch. The runtime, which declares this pallet with
+/// `#[macros::runtime(sudo = ..)]`, then dispatches it, and reports the result back with
+/// `note_call_result`.
+pub struct Pallet<T: Config> {
+	/// The account holding the sudo key.
+	key: StorageValue<T::AccountId>,
+	/// The call to dispatch, and its origin, until the runtime takes it.
+	call: Option<(T::RuntimeOrigin, T::RuntimeCall)>,
+	/// Events deposited by this pallet, which are yet to be collected by the runtime.
+	events: TransactionalValue<Vec<Event<T>>>,
+}
+
+// `#[derive(Debug)]` would require `T: Debug`, and the call to dispatch is never pending outside of a
+// dispatch anyway.
+impl<T: Config> std::fmt::Debug for Pallet<T>
+where
+	T::AccountId: std::fmt::Debug,
+	Event<T>: std::fmt::Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("Pallet")
+			.field("key", &self.key)
+			.field("events", &self.events)
+			.finish()
+	}
+}
+
+#[macros::event]
+pub enum Event<T: Config> {
+	/// A call was dispatched by the sudo key, with this result.
+	Sudid { result: DispatchResult },
+	/// The sudo key was handed over from `old` to `new`.
+	KeyChanged { old: Option<T::AccountId>, new: T::AccountId },
+}
+
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The caller does not hold the sudo key.
+	RequireSudo,
+}
+
+impl<T: Config> Pallet<T> {
+	pub fn new(storage: &Storage) -> Self {
+		Self {
+			key: storage_item!(StorageValue<T::AccountId>, storage, "Sudo", "Key"),
+			call: None,
+			events: TransactionalValue::new(Vec::new()),
+		}
+	}
+
+	pub fn deposit_event(&mut self, event: Event<T>) {
+		self.events.get_mut().push(event);
+	}
+
+	/// Take all the events deposited so far, see `system::Pallet::deposit_event`.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		std::mem::take(self.events.get_mut())
+	}
+
+	/// The account holding the sudo key, if any.
+	pub fn key(&self) -> Option<T::AccountId> {
+		self.key.get()
+	}
+
+	/// Take the call which is waiting to be dispatched by the runtime, with its origin.
+	pub fn take_call(&mut self) -> Option<(T::RuntimeOrigin, T::RuntimeCall)> {
+		self.call.take()
+	}
+
+	/// Record the result of a call taken with `take_call`.
+	pub fn note_call_result(&mut self, result: DispatchResult) {
+		self.deposit_event(Event::Sudid { result });
+	}
+
+	fn ensure_sudo(&self, who: &T::AccountId) -> DispatchResult {
+		match self.key() {
+			Some(key) if &key == who => Ok(()),
+			_ => Err(Error::RequireSudo.into()),
+		}
+	}
+}
+
+impl<T: Config> Transactional for Pallet<T> {
+	fn start_transaction(&mut self) {
+		self.events.start_transaction();
+	}
+
+	fn commit_transaction(&mut self) {
+		self.events.commit_transaction();
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.events.rollback_transaction();
+	}
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+// The calls are boxed, since `RuntimeCall` includes the calls of this pallet.
+#[allow(clippy::boxed_local)]
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Dispatch `call` with the root origin. Its result is recorded in a `Sudid` event.
+	#[call_index(0)]
+	#[weight(GetDispatchInfo::get_dispatch_info(&**call).weight.saturating_add(1_000))]
+	pub fn sudo(&mut self, caller: T::AccountId, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_sudo(&caller)?;
+		self.call = Some((RawOrigin::Root.into(), *call));
+		Ok(())
+	}
+
+	/// Dispatch `call` with the signed origin of `who`. Its result is recorded in a `Sudid` event.
+	#[call_index(1)]
+	#[weight(GetDispatchInfo::get_dispatch_info(&**call).weight.saturating_add(1_000))]
+	pub fn sudo_as(&mut self, caller: T::AccountId, who: T::AccountId, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_sudo(&caller)?;
+		self.call = Some((RawOrigin::Signed(who).into(), *call));
+		Ok(())
+	}
+
+	/// Hand the sudo key over to `new`.
+	#[call_index(2)]
+	#[weight(1_000)]
+	pub fn set_key(&mut self, caller: T::AccountId, new: T::AccountId) -> DispatchResult {
+		self.ensure_sudo(&caller)?;
+		let old = self.key.get();
+		self.key.set(new.clone());
+		self.deposit_event(Event::KeyChanged { old, new });
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		support::{DispatchInfo, Encode, GetDispatchInfo},
+		system::RawOrigin,
+	};
+
+	#[derive(Debug, Clone, PartialEq)]
+	struct TestCall(u32);
+
+	impl Encode for TestCall {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			self.0.encode_to(dest)
+		}
+	}
+
+	impl GetDispatchInfo for TestCall {
+		fn get_dispatch_info(&self) -> DispatchInfo {
+			DispatchInfo { weight: 10 }
+		}
+	}
+
+	struct TestConfig;
+
+	impl super::Config for TestConfig {
+		type RuntimeCall = TestCall;
+	}
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type Signature = crate::support::crypto::TestSignature<String>;
+		type Nonce = u32;
+		type BlockNumber = u32;
+		type Hash = crate::support::crypto::H256;
+		type Hashing = crate::support::crypto::Sha256;
+		type RuntimeOrigin = RawOrigin<Self::AccountId>;
+		type RuntimeEvent = ();
+
+		const BLOCK_HASH_COUNT: u32 = 250;
+		const RECEIPT_COUNT: u32 = 250;
+		const MAX_BLOCK_WEIGHT: u64 = 1_000_000;
+		const MAX_CHECKPOINTS: u32 = 0;
+	}
+
+	#[test]
+	fn sudo_calls() {
+		let mut sudo = super::Pallet::<TestConfig>::new(&Default::default());
+		let alice = "alice".to_string();
+		let bob = "bob".to_string();
+
+		// Nobody holds the key yet.
+		assert_eq!(sudo.sudo(alice.clone(), Box::new(TestCall(1))), Err(super::Error::RequireSudo.into()));
+		crate::support::BuildGenesisConfig::build(&super::GenesisConfig { key: Some(alice.clone()) }, &mut sudo);
+
+		assert_eq!(sudo.sudo(alice.clone(), Box::new(TestCall(1))), Ok(()));
+		assert_eq!(sudo.take_call(), Some((RawOrigin::Root, TestCall(1))));
+		assert_eq!(sudo.take_call(), None);
+
+		assert_eq!(sudo.sudo_as(alice.clone(), bob.clone(), Box::new(TestCall(2))), Ok(()));
+		assert_eq!(sudo.take_call(), Some((RawOrigin::Signed(bob.clone()), TestCall(2))));
+		sudo.note_call_result(Ok(()));
+
+		assert_eq!(sudo.set_key(alice.clone(), bob.clone()), Ok(()));
+		assert_eq!(sudo.key(), Some(bob.clone()));
+		assert_eq!(sudo.sudo(alice.clone(), Box::new(TestCall(3))), Err(super::Error::RequireSudo.into()));
+		assert_eq!(
+			sudo.take_events(),
+			vec![super::Event::Sudid { result: Ok(()) }, super::Event::KeyChanged { old: Some(alice), new: bob }]
+		);
+	}
+}
@@ -1,22 +1,330 @@
+pub mod codec;
+pub mod crypto;
+pub mod metadata;
+pub mod storage;
+pub mod transactional;
+
+pub use codec::{Decode, Encode};
+pub use crypto::{Hash, Verify};
+pub use storage::{Storage, StorageMap, StorageValue};
+pub use transactional::{with_transaction, Transactional, TransactionalValue};
+
+#[derive(Clone, macros::Encode, macros::Decode)]
 pub struct Block<Header, Extrinsic> {
 	pub header: Header,
 	pub extrinsics: Vec<Extrinsic>,
 }
 
-pub struct Header<BlockNumber> {
+#[derive(Clone, macros::Encode, macros::Decode)]
+pub struct Header<BlockNumber, Hash, AccountId> {
+	/// The hash of the header of the block this block is built on.
+	pub parent_hash: Hash,
 	pub block_number: BlockNumber,
+	/// The root of all the runtime storage after executing this block, see `storage::state_root`.
+	pub state_root: Hash,
+	/// The root of the extrinsics of this block, see `crypto::merkle_root`.
+	pub extrinsics_root: Hash,
+	/// The account which authored this block, if any. It can be rewarded with the fees of the
+	/// block, see `transaction_payment::ToBlockAuthor`.
+	pub author: Option<AccountId>,
+}
+
+impl<BlockNumber: Encode, Hash: Encode, AccountId: Encode> Header<BlockNumber, Hash, AccountId> {
+	/// The hash of this header, which identifies the block.
+	pub fn hash<Hashing: crypto::Hash<Output = Hash>>(&self) -> Hash {
+		Hashing::hash(&self.encode())
+	}
 }
 
-pub struct Extrinsic<Caller, Call> {
-	pub caller: Caller,
+/// A signed extrinsic. The caller is the `signer`, which is only trusted once `signature` has been
+/// verified against the `signing_payload`.
+#[derive(Clone, macros::Encode, macros::Decode)]
+pub struct Extrinsic<Signer, Nonce, Call, Signature> {
+	pub signer: Signer,
+	pub signature: Signature,
+	/// Must match the caller's current nonce in the system pallet, which protects against replays.
+	pub nonce: Nonce,
 	pub call: Call,
 }
 
-pub type DispatchResult = Result<(), &'static str>;
+impl<Signer, Nonce: Encode, Call: Encode, Signature> Extrinsic<Signer, Nonce, Call, Signature> {
+	/// The bytes covered by the signature: the encoded call followed by the encoded nonce.
+	pub fn signing_payload(call: &Call, nonce: &Nonce) -> Vec<u8> {
+		(call, nonce).encode()
+	}
+}
+
+pub type DispatchResult = Result<(), DispatchError>;
+
+/// The cost of executing a call. Blocks can only contain a limited amount of weight, see
+/// `system::Config::MAX_BLOCK_WEIGHT`.
+pub type Weight = u64;
+
+/// Information about a call which is known before dispatching it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchInfo {
+	pub weight: Weight,
+}
+
+/// Whether a block can include extrinsics whose call fails. Each runtime chooses with
+/// `#[macros::runtime(failed_extrinsics = ..)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailedExtrinsics {
+	/// A failing extrinsic is included, and its failure is recorded in the receipt of the block.
+	Include,
+	/// A failing extrinsic makes the whole block invalid.
+	#[allow(dead_code)]
+	InvalidateBlock,
+}
+
+impl FailedExtrinsics {
+	/// Whether a block can include an extrinsic which was applied with `result`. The policy only
+	/// decides about extrinsics whose call failed: an invalid transaction is never allowed.
+	pub fn allows(self, result: &ApplyExtrinsicResult) -> bool {
+		match result {
+			Ok(Ok(())) => true,
+			Ok(Err(_)) => self == FailedExtrinsics::Include,
+			Err(_) => false,
+		}
+	}
+}
+
+/// The outcome of executing a block, see `Runtime::execute_block`.
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct BlockReceipt<Event> {
+	/// The receipt of every extrinsic of the block, in the order of the block.
+	pub extrinsics: Vec<ExtrinsicReceipt<Event>>,
+}
+
+/// The outcome of applying an extrinsic of a block.
+#[derive(Debug, Clone, PartialEq, macros::Encode, macros::Decode)]
+pub struct ExtrinsicReceipt<Event> {
+	/// Whether the extrinsic could be applied, and if so, the result of its call.
+	pub result: ApplyExtrinsicResult,
+	/// The events deposited while applying the extrinsic, including those of its fee.
+	pub events: Vec<Event>,
+	/// The weight the extrinsic consumed from the block, which is 0 if it could not be applied.
+	pub weight: Weight,
+}
+
+/// The effects a call would have if it was dispatched on top of the current state, see
+/// `Runtime::dry_run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRun<Event> {
+	pub result: DispatchResult,
+	/// The events deposited by the call.
+	pub events: Vec<Event>,
+	/// The weight of the call, see `GetDispatchInfo`.
+	pub weight: Weight,
+	/// The writes the call would make to the storage, see `Storage::overlay_changes`. A failing call
+	/// makes no writes.
+	pub storage_changes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+/// Calls which know their `DispatchInfo`, see the `#[weight(..)]` attribute of `#[macros::call]`.
+pub trait GetDispatchInfo {
+	fn get_dispatch_info(&self) -> DispatchInfo;
+}
+
+/// Functions a pallet can run at specific points in the execution of every block.
+///
+/// The runtime calls the hooks of every pallet in the order the pallets are declared in the
+/// `Runtime` struct. All of them do nothing by default.
+pub trait Hooks<BlockNumber> {
+	/// Called at the start of block `block_number`, before any extrinsic is applied. Returns the
+	/// weight it consumed, which is taken from the block.
+	fn on_initialize(&mut self, _block_number: BlockNumber) -> Weight {
+		0
+	}
+
+	/// Called after all the extrinsics of the block are applied, with the weight which is left in
+	/// the block. Returns the weight it consumed, which should not exceed `remaining_weight`.
+	fn on_idle(&mut self, _remaining_weight: Weight) -> Weight {
+		0
+	}
+
+	/// Called at the end of block `block_number`, after `on_idle`.
+	fn on_finalize(&mut self, _block_number: BlockNumber) {}
+}
+
+/// The initial state of a pallet, which is part of the `RuntimeGenesisConfig` generated by
+/// `#[macros::runtime]`.
+pub trait BuildGenesisConfig<Pallet> {
+	/// Write the initial state to a newly created `pallet`.
+	fn build(&self, pallet: &mut Pallet);
+}
+
+/// The reasons a dispatched call can fail.
+///
+/// Messages are not encoded, so a decoded `Other` error has an empty message, see `ModuleError`
+/// for the name of a decoded `Module` error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchError {
+	/// Some other error, described by a message.
+	Other(&'static str),
+	/// The origin of the call is not allowed to make it.
+	BadOrigin,
+	/// An arithmetic operation failed.
+	Arithmetic(ArithmeticError),
+	/// An error defined by a pallet, see `#[macros::error]`.
+	Module(ModuleError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, macros::Encode, macros::Decode)]
+pub enum ArithmeticError {
+	/// The result is larger than the maximum value of its type.
+	Overflow,
+	/// The result is smaller than the minimum value of its type.
+	#[allow(dead_code)]
+	Underflow,
+}
+
+/// An error defined by a pallet.
+///
+/// Only the indices identify the error: they are all that is encoded, and all that is compared.
+/// The name of a decoded error can be found with `Runtime::describe_error`.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleError {
+	/// The index of the pallet in the runtime, once the runtime attributed the error to the pallet
+	/// whose call returned it, see `DispatchError::in_pallet`.
+	pub pallet_index: Option<u8>,
+	/// The index of the error in the `Error` enum of the pallet.
+	pub error_index: u8,
+	/// The name of the error, if known.
+	pub message: Option<&'static str>,
+}
+
+impl PartialEq for ModuleError {
+	fn eq(&self, other: &Self) -> bool {
+		self.pallet_index == other.pallet_index && self.error_index == other.error_index
+	}
+}
+
+impl Eq for ModuleError {}
+
+impl Encode for ModuleError {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(self.pallet_index, self.error_index).encode_to(dest)
+	}
+}
+
+impl Decode for ModuleError {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		let (pallet_index, error_index) = Decode::decode(input)?;
+		Ok(ModuleError { pallet_index, error_index, message: None })
+	}
+}
+
+impl Encode for DispatchError {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			DispatchError::Other(_) => dest.push(0),
+			DispatchError::BadOrigin => dest.push(1),
+			DispatchError::Arithmetic(error) => {
+				dest.push(2);
+				error.encode_to(dest);
+			},
+			DispatchError::Module(error) => {
+				dest.push(3);
+				error.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl Decode for DispatchError {
+	fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		match u8::decode(input)? {
+			0 => Ok(DispatchError::Other("")),
+			1 => Ok(DispatchError::BadOrigin),
+			2 => Ok(DispatchError::Arithmetic(Decode::decode(input)?)),
+			3 => Ok(DispatchError::Module(Decode::decode(input)?)),
+			_ => Err(codec::Error("invalid dispatch error")),
+		}
+	}
+}
+
+impl DispatchError {
+	/// Attribute a `Module` error to the pallet at `pallet_index` in the runtime, unless it is
+	/// attributed already, e.g. by the dispatch of a call nested in the call of that pallet.
+	pub fn in_pallet(self, pallet_index: u8) -> Self {
+		match self {
+			DispatchError::Module(error @ ModuleError { pallet_index: None, .. }) => {
+				DispatchError::Module(ModuleError { pallet_index: Some(pallet_index), ..error })
+			},
+			other => other,
+		}
+	}
+}
+
+impl From<&'static str> for DispatchError {
+	fn from(message: &'static str) -> Self {
+		DispatchError::Other(message)
+	}
+}
+
+impl From<ArithmeticError> for DispatchError {
+	fn from(error: ArithmeticError) -> Self {
+		DispatchError::Arithmetic(error)
+	}
+}
+
+impl std::fmt::Display for DispatchError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			DispatchError::Other(message) => write!(f, "{}", message),
+			DispatchError::BadOrigin => write!(f, "Bad origin"),
+			DispatchError::Arithmetic(error) => write!(f, "Arithmetic error: {:?}", error),
+			DispatchError::Module(ModuleError { pallet_index, error_index, message }) => {
+				let message = message.unwrap_or("Module error");
+				match pallet_index {
+					Some(pallet_index) => write!(f, "{} (pallet {}, error {})", message, pallet_index, error_index),
+					None => write!(f, "{} (error {})", message, error_index),
+				}
+			},
+		}
+	}
+}
+
+/// The reasons an extrinsic can be rejected before it is ever dispatched.
+///
+/// Unlike a failed dispatch, a rejected extrinsic has no effect on the state, not even on the
+/// caller's nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, macros::Encode, macros::Decode)]
+pub enum InvalidTransaction {
+	/// The nonce is lower than the caller's current nonce, e.g. the extrinsic is replayed.
+	Stale,
+	/// The nonce is higher than the caller's current nonce, so the extrinsic cannot be applied yet.
+	Future,
+	/// The signature does not match the signer and the signing payload.
+	BadProof,
+	/// The weight of the call does not fit in what is left of the block.
+	ExhaustsResources,
+	/// The caller can't pay the fee of the extrinsic.
+	Payment,
+}
+
+/// The priority of a valid transaction. Transactions with a higher priority are included first.
+pub type TransactionPriority = u64;
+
+/// Information about a transaction which could be included in a block on top of the current
+/// state, possibly after other transactions of the same signer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidTransaction {
+	pub priority: TransactionPriority,
+}
+
+/// The outcome of validating a transaction before it is included in a block.
+pub type TransactionValidity = Result<ValidTransaction, InvalidTransaction>;
+
+/// The outcome of applying a single extrinsic: either it was rejected, or it was dispatched with
+/// the inner `DispatchResult`.
+pub type ApplyExtrinsicResult = Result<DispatchResult, InvalidTransaction>;
 
 pub trait Dispatch {
-	type Caller;
+	/// Who the call is dispatched on behalf of, see `system::RawOrigin`.
+	type Origin;
 	type Call;
 
-	fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
+	fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> DispatchResult;
 }
@@ -0,0 +1,524 @@
+use std::collections::BTreeMap;
+
+/// Types which can be turned into a deterministic sequence of bytes.
+///
+/// This is what gets signed, hashed and stored, see `Decode` for the way back. Integers are little
+/// endian, and variable length types are prefixed with their length as a `Compact<u32>`.
+///
+/// Structs and enums can derive it with `#[derive(macros::Encode)]`.
+pub trait Encode {
+	/// Append the encoding of `self` to `dest`.
+	fn encode_to(&self, dest: &mut Vec<u8>);
+
+	/// Return the encoding of `self`.
+	fn encode(&self) -> Vec<u8> {
+		let mut dest = Vec::new();
+		self.encode_to(&mut dest);
+		dest
+	}
+}
+
+macro_rules! impl_encode_for_int {
+	( $( $int:ty ),* ) => {
+		$(
+			impl Encode for $int {
+				fn encode_to(&self, dest: &mut Vec<u8>) {
+					dest.extend_from_slice(&self.to_le_bytes());
+				}
+			}
+		)*
+	};
+}
+
+impl_encode_for_int!(u8, u16, u32, u64, u128);
+
+impl Encode for bool {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.push(*self as u8);
+	}
+}
+
+/// Encode the length prefix of a variable length type.
+fn encode_len(len: usize, dest: &mut Vec<u8>) {
+	Compact(u32::try_from(len).expect("lengths fit in a u32")).encode_to(dest);
+}
+
+impl Encode for str {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		dest.extend_from_slice(self.as_bytes());
+	}
+}
+
+impl Encode for String {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_str().encode_to(dest)
+	}
+}
+
+impl<T: Encode> Encode for [T] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Encode> Encode for Vec<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_slice().encode_to(dest)
+	}
+}
+
+/// Maps are encoded like a `Vec` of their `(key, value)` pairs, ordered by key.
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		for (key, value) in self {
+			key.encode_to(dest);
+			value.encode_to(dest);
+		}
+	}
+}
+
+/// Fixed size arrays have a known length, so no length prefix is needed.
+impl<T: Encode, const N: usize> Encode for [T; N] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Encode> Encode for Option<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			None => dest.push(0),
+			Some(value) => {
+				dest.push(1);
+				value.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl<T: Encode, E: Encode> Encode for Result<T, E> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			Ok(value) => {
+				dest.push(0);
+				value.encode_to(dest);
+			},
+			Err(error) => {
+				dest.push(1);
+				error.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl Encode for () {
+	fn encode_to(&self, _dest: &mut Vec<u8>) {}
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest)
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest)
+	}
+}
+
+macro_rules! impl_encode_for_tuple {
+	( $( $name:ident ),+ ) => {
+		impl<$( $name: Encode ),+> Encode for ( $( $name, )+ ) {
+			#[allow(non_snake_case)]
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				let ( $( $name, )+ ) = self;
+				$( $name.encode_to(dest); )+
+			}
+		}
+	};
+}
+
+impl_encode_for_tuple!(A);
+impl_encode_for_tuple!(A, B);
+impl_encode_for_tuple!(A, B, C);
+impl_encode_for_tuple!(A, B, C, D);
+
+/// The reason some bytes are not a valid encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error(pub &'static str);
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "Invalid encoding: {}", self.0)
+	}
+}
+
+/// Types which can be read back from their `Encode` representation.
+///
+/// Structs and enums can derive it with `#[derive(macros::Decode)]`.
+pub trait Decode: Sized {
+	/// Decode a value from the start of `input`, advancing `input` past the bytes which were read.
+	fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+
+	/// Decode a value from `bytes`, which must not contain anything else.
+	fn decode_all(mut bytes: &[u8]) -> Result<Self, Error> {
+		let value = Self::decode(&mut bytes)?;
+		if !bytes.is_empty() {
+			return Err(Error("trailing bytes"));
+		}
+		Ok(value)
+	}
+}
+
+/// How deeply values which can hold values of their own type, e.g. calls holding other calls, can
+/// be nested in an encoding, see `decode_nested`.
+pub const MAX_DECODE_DEPTH: u32 = 256;
+
+thread_local! {
+	/// The number of `decode_nested` calls currently running on this thread.
+	static DECODE_DEPTH: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+}
+
+/// Run `decode`, which decodes a value that can hold values of its own type, e.g. a call holding
+/// another call. Once such values are nested more than `MAX_DECODE_DEPTH` deep, decoding fails
+/// instead of overflowing the stack on a malicious input.
+pub fn decode_nested<T>(decode: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+	let depth = DECODE_DEPTH.with(|depth| depth.get());
+	if depth >= MAX_DECODE_DEPTH {
+		return Err(Error("values are nested too deeply"));
+	}
+	DECODE_DEPTH.with(|cell| cell.set(depth + 1));
+	let result = decode();
+	DECODE_DEPTH.with(|cell| cell.set(depth));
+	result
+}
+
+/// Take the next `len` bytes of `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+	if input.len() < len {
+		return Err(Error("not enough bytes"));
+	}
+	let (bytes, rest) = input.split_at(len);
+	*input = rest;
+	Ok(bytes)
+}
+
+macro_rules! impl_decode_for_int {
+	( $( $int:ty ),* ) => {
+		$(
+			impl Decode for $int {
+				fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+					let bytes = take(input, std::mem::size_of::<$int>())?;
+					Ok(<$int>::from_le_bytes(bytes.try_into().expect("the slice has the size of the integer")))
+				}
+			}
+		)*
+	};
+}
+
+impl_decode_for_int!(u8, u16, u32, u64, u128);
+
+impl Decode for bool {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(Error("invalid bool")),
+		}
+	}
+}
+
+/// Decode the length prefix of a variable length type.
+fn decode_len(input: &mut &[u8]) -> Result<usize, Error> {
+	Ok(Compact::<u32>::decode(input)?.0 as usize)
+}
+
+impl Decode for String {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		let bytes = take(input, len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| Error("invalid utf-8"))
+	}
+}
+
+impl<T: Decode> Decode for Vec<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		// The length is not trusted to pre-allocate, every item needs at least one byte anyway.
+		let mut items = Vec::with_capacity(len.min(input.len()));
+		for _ in 0..len {
+			items.push(T::decode(input)?);
+		}
+		Ok(items)
+	}
+}
+
+impl<K: Decode + Ord, V: Decode> Decode for BTreeMap<K, V> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(Vec::<(K, V)>::decode(input)?.into_iter().collect())
+	}
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let mut items = Vec::with_capacity(N);
+		for _ in 0..N {
+			items.push(T::decode(input)?);
+		}
+		Ok(items
+			.try_into()
+			.unwrap_or_else(|_| unreachable!("exactly N items were decoded")))
+	}
+}
+
+impl<T: Decode> Decode for Option<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(None),
+			1 => Ok(Some(T::decode(input)?)),
+			_ => Err(Error("invalid option")),
+		}
+	}
+}
+
+impl<T: Decode, E: Decode> Decode for Result<T, E> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(Ok(T::decode(input)?)),
+			1 => Ok(Err(E::decode(input)?)),
+			_ => Err(Error("invalid result")),
+		}
+	}
+}
+
+impl Decode for () {
+	fn decode(_input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(())
+	}
+}
+
+impl<T: Decode> Decode for Box<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(Box::new(T::decode(input)?))
+	}
+}
+
+macro_rules! impl_decode_for_tuple {
+	( $( $name:ident ),+ ) => {
+		impl<$( $name: Decode ),+> Decode for ( $( $name, )+ ) {
+			fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+				Ok(( $( $name::decode(input)?, )+ ))
+			}
+		}
+	};
+}
+
+impl_decode_for_tuple!(A);
+impl_decode_for_tuple!(A, B);
+impl_decode_for_tuple!(A, B, C);
+impl_decode_for_tuple!(A, B, C, D);
+
+/// An unsigned integer with a compact encoding, which is shorter for smaller values.
+///
+/// The two lowest bits of the first byte give the mode: values below `2^6` take a single byte,
+/// below `2^14` two bytes, below `2^30` four bytes, all holding the value shifted by two bits.
+/// Larger values are encoded with the number of bytes which follow the first byte, minus 4, in its
+/// upper six bits, followed by the value itself in as few bytes as possible. Every value has a
+/// single valid encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compact<T>(pub T);
+
+fn encode_compact(value: u128, dest: &mut Vec<u8>) {
+	match value {
+		0..=0x3f => dest.push((value as u8) << 2),
+		0x40..=0x3fff => dest.extend_from_slice(&(((value as u16) << 2) | 0b01).to_le_bytes()),
+		0x4000..=0x3fff_ffff => dest.extend_from_slice(&(((value as u32) << 2) | 0b10).to_le_bytes()),
+		_ => {
+			let len = 16 - value.leading_zeros() as usize / 8;
+			dest.push((((len - 4) as u8) << 2) | 0b11);
+			dest.extend_from_slice(&value.to_le_bytes()[..len]);
+		},
+	}
+}
+
+fn decode_compact(input: &mut &[u8]) -> Result<u128, Error> {
+	let first = u8::decode(input)?;
+	let (value, min) = match first & 0b11 {
+		0b00 => return Ok((first >> 2) as u128),
+		0b01 => ((u16::from_le_bytes([first, u8::decode(input)?]) >> 2) as u128, 0x40),
+		0b10 => {
+			let rest = take(input, 3)?;
+			((u32::from_le_bytes([first, rest[0], rest[1], rest[2]]) >> 2) as u128, 0x4000)
+		},
+		_ => {
+			let len = (first >> 2) as usize + 4;
+			if len > 16 {
+				return Err(Error("compact integer out of range"));
+			}
+			let mut bytes = [0; 16];
+			bytes[..len].copy_from_slice(take(input, len)?);
+			let value = u128::from_le_bytes(bytes);
+			// The value must need all of its bytes.
+			if bytes[len - 1] == 0 {
+				return Err(Error("non-canonical compact integer"));
+			}
+			(value, 0x4000_0000)
+		},
+	};
+	if value < min {
+		return Err(Error("non-canonical compact integer"));
+	}
+	Ok(value)
+}
+
+macro_rules! impl_codec_for_compact {
+	( $( $int:ty ),* ) => {
+		$(
+			impl Encode for Compact<$int> {
+				fn encode_to(&self, dest: &mut Vec<u8>) {
+					encode_compact(self.0 as u128, dest);
+				}
+			}
+
+			impl Decode for Compact<$int> {
+				fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+					let value = decode_compact(input)?;
+					<$int>::try_from(value).map(Compact).map_err(|_| Error("compact integer out of range"))
+				}
+			}
+		)*
+	};
+}
+
+impl_codec_for_compact!(u32, u64, u128);
+
+#[cfg(test)]
+mod test {
+	use super::{Compact, Decode, Encode, Error};
+
+	#[test]
+	fn encode_primitives() {
+		assert_eq!(1u32.encode(), vec![1, 0, 0, 0]);
+		assert_eq!(true.encode(), vec![1]);
+		assert_eq!("ab".encode(), vec![8, b'a', b'b']);
+		assert_eq!(vec![1u8, 2].encode(), vec![8, 1, 2]);
+		assert_eq!([1u8, 2].encode(), vec![1, 2]);
+		assert_eq!(Some(1u8).encode(), vec![1, 1]);
+		assert_eq!(None::<u8>.encode(), vec![0]);
+		assert_eq!((1u8, 2u16).encode(), vec![1, 2, 0]);
+		assert_eq!(Ok::<u8, u16>(1).encode(), vec![0, 1]);
+		assert_eq!(Err::<u8, u16>(1).encode(), vec![1, 1, 0]);
+		assert_eq!(().encode(), Vec::<u8>::new());
+	}
+
+	#[test]
+	fn decode_primitives() {
+		assert_eq!(u32::decode_all(&[1, 0, 0, 0]), Ok(1));
+		assert_eq!(String::decode_all(&"ab".encode()), Ok("ab".to_string()));
+		assert_eq!(Vec::<u16>::decode_all(&vec![1u16, 2].encode()), Ok(vec![1, 2]));
+		assert_eq!(<[u8; 2]>::decode_all(&[1, 2]), Ok([1, 2]));
+		assert_eq!(Option::<u8>::decode_all(&[1, 1]), Ok(Some(1)));
+		assert_eq!(<(u8, bool)>::decode_all(&[1, 0]), Ok((1, false)));
+		assert_eq!(Result::<u8, u16>::decode_all(&[1, 1, 0]), Ok(Err(1)));
+		let map = std::collections::BTreeMap::from([(2u8, true), (1, false)]);
+		assert_eq!(map.encode(), vec![8, 1, 0, 2, 1]);
+		assert_eq!(std::collections::BTreeMap::decode_all(&map.encode()), Ok(map));
+		assert_eq!(<()>::decode_all(&[]), Ok(()));
+
+		assert_eq!(u32::decode_all(&[1, 0]), Err(Error("not enough bytes")));
+		assert_eq!(u8::decode_all(&[1, 0]), Err(Error("trailing bytes")));
+		assert_eq!(bool::decode_all(&[2]), Err(Error("invalid bool")));
+		assert_eq!(Result::<u8, u8>::decode_all(&[2, 0]), Err(Error("invalid result")));
+		// A huge length prefix does not allocate, it just runs out of bytes.
+		assert_eq!(Vec::<u8>::decode_all(&Compact(u32::MAX).encode()), Err(Error("not enough bytes")));
+	}
+
+	#[test]
+	fn compact_integers() {
+		let cases: [(u128, &[u8]); 7] = [
+			(0, &[0]),
+			(63, &[0xfc]),
+			(64, &[0x01, 0x01]),
+			(16383, &[0xfd, 0xff]),
+			(16384, &[0x02, 0x00, 0x01, 0x00]),
+			(1 << 30, &[0x03, 0x00, 0x00, 0x00, 0x40]),
+			(u64::MAX as u128, &[0x13, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+		];
+		for (value, encoded) in cases {
+			assert_eq!(Compact(value).encode(), encoded);
+			assert_eq!(Compact::<u128>::decode_all(encoded), Ok(Compact(value)));
+		}
+		assert_eq!(Compact(u128::MAX).encode().len(), 17);
+		assert_eq!(Compact::<u128>::decode_all(&Compact(u128::MAX).encode()), Ok(Compact(u128::MAX)));
+
+		// Values must fit in the type, and use the shortest encoding.
+		assert_eq!(
+			Compact::<u32>::decode_all(&Compact(1u64 << 32).encode()),
+			Err(Error("compact integer out of range"))
+		);
+		assert_eq!(Compact::<u32>::decode_all(&[0x01, 0x00]), Err(Error("non-canonical compact integer")));
+		assert_eq!(
+			Compact::<u32>::decode_all(&[0x03, 0x01, 0x00, 0x00, 0x00]),
+			Err(Error("non-canonical compact integer"))
+		);
+	}
+
+	#[derive(Debug, PartialEq, macros::Encode, macros::Decode)]
+	struct Point<T> {
+		x: T,
+		y: T,
+	}
+
+	#[derive(Debug, PartialEq, macros::Encode, macros::Decode)]
+	enum Shape {
+		Empty,
+		Line(Point<u8>, Point<u8>),
+		#[codec(index = 7)]
+		Circle {
+			center: Point<u8>,
+			radius: u32,
+		},
+	}
+
+	#[test]
+	fn nested_calls_are_limited() {
+		use crate::{balances, sudo, support::crypto::ed25519::Pair, RuntimeCall};
+
+		let mut call =
+			RuntimeCall::balances(balances::Call::transfer { to: Pair::from_name("bob").public(), amount: 1 });
+		for _ in 0..10 {
+			call = RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(call) });
+		}
+		let bytes = call.encode();
+		assert_eq!(RuntimeCall::decode_all(&bytes).map(|call| call.encode()), Ok(bytes.clone()));
+
+		// Every level of `sudo` only takes two bytes: the index of the pallet and of the call.
+		let mut nested = [4u8, 0].repeat(1_000_000);
+		nested.extend_from_slice(&bytes);
+		assert_eq!(RuntimeCall::decode_all(&nested).err(), Some(Error("values are nested too deeply")));
+		// The depth is restored after the failure.
+		assert!(RuntimeCall::decode_all(&bytes).is_ok());
+	}
+
+	#[test]
+	fn derived_codec() {
+		assert_eq!(Point { x: 1u8, y: 2 }.encode(), vec![1, 2]);
+		assert_eq!(Shape::Empty.encode(), vec![0]);
+		assert_eq!(Shape::Line(Point { x: 1, y: 2 }, Point { x: 3, y: 4 }).encode(), vec![1, 1, 2, 3, 4]);
+		let circle = Shape::Circle { center: Point { x: 1, y: 2 }, radius: 3 };
+		assert_eq!(circle.encode(), vec![7, 1, 2, 3, 0, 0, 0]);
+
+		for shape in [Shape::Empty, Shape::Line(Point { x: 1, y: 2 }, Point { x: 3, y: 4 }), circle] {
+			assert_eq!(Shape::decode_all(&shape.encode()), Ok(shape));
+		}
+		assert_eq!(Shape::decode_all(&[2]), Err(Error("unknown variant index")));
+	}
+}
@@ -0,0 +1,272 @@
+/// A signature which can be checked against a message and the public key of its signer.
+///
+/// This is what lets each runtime pick its own signature scheme, see `system::Config::Signature`.
+pub trait Verify {
+	/// The public key of whoever produced the signature.
+	type Signer;
+
+	/// Return `true` if `self` is a valid signature of `msg` by `signer`.
+	fn verify(&self, msg: &[u8], signer: &Self::Signer) -> bool;
+}
+
+/// A hash function, producing fixed size digests of arbitrary data.
+pub trait Hash {
+	/// The digest produced by this hash function.
+	type Output;
+
+	fn hash(data: &[u8]) -> Self::Output;
+}
+
+/// A 256 bit hash.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct H256(pub [u8; 32]);
+
+impl crate::support::Encode for H256 {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest)
+	}
+}
+
+impl crate::support::Decode for H256 {
+	fn decode(input: &mut &[u8]) -> Result<Self, crate::support::codec::Error> {
+		Ok(H256(<[u8; 32]>::decode(input)?))
+	}
+}
+
+impl std::fmt::Debug for H256 {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		fmt_hex(&self.0, f)
+	}
+}
+
+/// The SHA-256 hash function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha256;
+
+impl Hash for Sha256 {
+	type Output = H256;
+
+	fn hash(data: &[u8]) -> H256 {
+		use sha2::Digest;
+		H256(sha2::Sha256::digest(data).into())
+	}
+}
+
+/// Compute the root of a binary Merkle tree over `leaves`.
+///
+/// Leaves and inner nodes are hashed with a different prefix, so one can never be mistaken for the
+/// other. An odd node at the end of a level is carried up unchanged, and the root of an empty tree
+/// is the hash of no data.
+pub fn merkle_root<Hashing>(leaves: impl IntoIterator<Item = Vec<u8>>) -> Hashing::Output
+where
+	Hashing: Hash,
+	Hashing::Output: crate::support::Encode,
+{
+	use crate::support::Encode;
+
+	let mut level = leaves
+		.into_iter()
+		.map(|leaf| Hashing::hash(&(0u8, leaf).encode()))
+		.collect::<Vec<_>>();
+	if level.is_empty() {
+		return Hashing::hash(&[]);
+	}
+
+	while level.len() > 1 {
+		let mut nodes = level.into_iter();
+		level = Vec::new();
+		while let Some(left) = nodes.next() {
+			match nodes.next() {
+				Some(right) => level.push(Hashing::hash(&(1u8, &left, &right).encode())),
+				None => level.push(left),
+			}
+		}
+	}
+	level.pop().expect("a non empty level always has a root")
+}
+
+/// Format `bytes` as a `0x` prefixed hex string.
+fn fmt_hex(bytes: &[u8], f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "0x")?;
+	for byte in bytes {
+		write!(f, "{:02x}", byte)?;
+	}
+	Ok(())
+}
+
+/// Parse a `0x` prefixed hex string of exactly `N` bytes.
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+	let hex = hex.strip_prefix("0x")?;
+	if hex.len() != 2 * N {
+		return None;
+	}
+	let mut bytes = [0u8; N];
+	for (i, byte) in bytes.iter_mut().enumerate() {
+		*byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+	}
+	Some(bytes)
+}
+
+/// Ed25519 signatures, backed by `ed25519-dalek`.
+pub mod ed25519 {
+	use crate::support::{codec, Decode, Encode};
+	use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+	use sha2::{Digest, Sha256};
+
+	/// An ed25519 public key. This is also used as an account id.
+	#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct Public(pub [u8; 32]);
+
+	/// An ed25519 signature.
+	#[derive(Clone, Copy, PartialEq, Eq)]
+	pub struct Signature(pub [u8; 64]);
+
+	/// An ed25519 key pair, used to sign extrinsics.
+	pub struct Pair(SigningKey);
+
+	impl Pair {
+		pub fn from_seed(seed: &[u8; 32]) -> Self {
+			Self(SigningKey::from_bytes(seed))
+		}
+
+		/// Derive a key pair from a human readable name, e.g. `"alice"`.
+		///
+		/// Anyone who knows the name can derive the key, so this is only meant for development and
+		/// testing.
+		pub fn from_name(name: &str) -> Self {
+			Self::from_seed(&Sha256::digest(name.as_bytes()).into())
+		}
+
+		pub fn public(&self) -> Public {
+			Public(self.0.verifying_key().to_bytes())
+		}
+
+		pub fn sign(&self, msg: &[u8]) -> Signature {
+			Signature(self.0.sign(msg).to_bytes())
+		}
+	}
+
+	impl super::Verify for Signature {
+		type Signer = Public;
+
+		fn verify(&self, msg: &[u8], signer: &Public) -> bool {
+			let Ok(key) = VerifyingKey::from_bytes(&signer.0) else { return false };
+			key.verify_strict(msg, &ed25519_dalek::Signature::from_bytes(&self.0)).is_ok()
+		}
+	}
+
+	impl Encode for Public {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			self.0.encode_to(dest)
+		}
+	}
+
+	impl Decode for Public {
+		fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+			Ok(Public(<[u8; 32]>::decode(input)?))
+		}
+	}
+
+	impl Encode for Signature {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			self.0.encode_to(dest)
+		}
+	}
+
+	impl Decode for Signature {
+		fn decode(input: &mut &[u8]) -> Result<Self, codec::Error> {
+			Ok(Signature(<[u8; 64]>::decode(input)?))
+		}
+	}
+
+	/// Public keys are serialized as hex strings, e.g. in chain specs.
+	impl serde::Serialize for Public {
+		fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			serializer.collect_str(&format_args!("{:?}", self))
+		}
+	}
+
+	/// Public keys are parsed from hex strings, e.g. on the command line.
+	impl std::str::FromStr for Public {
+		type Err = &'static str;
+
+		fn from_str(hex: &str) -> Result<Self, Self::Err> {
+			super::parse_hex(hex)
+				.map(Public)
+				.ok_or("expected a 0x prefixed hex string of 32 bytes")
+		}
+	}
+
+	impl<'de> serde::Deserialize<'de> for Public {
+		fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+		}
+	}
+
+	impl std::fmt::Debug for Public {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			super::fmt_hex(&self.0, f)
+		}
+	}
+
+	impl std::fmt::Debug for Signature {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			super::fmt_hex(&self.0, f)
+		}
+	}
+}
+
+/// A signature scheme for tests, where a signature is valid if it names its signer.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestSignature<Signer>(pub Signer);
+
+#[cfg(test)]
+impl<Signer: PartialEq> Verify for TestSignature<Signer> {
+	type Signer = Signer;
+
+	fn verify(&self, _msg: &[u8], signer: &Signer) -> bool {
+		&self.0 == signer
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{ed25519::Pair, Hash, Sha256, Verify};
+
+	#[test]
+	fn sha256() {
+		let digest = Sha256::hash(b"abc");
+		assert_eq!(format!("{:?}", digest), "0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+	}
+
+	#[test]
+	fn merkle_root() {
+		let leaves = |count: u8| (0..count).map(|i| vec![i]).collect::<Vec<_>>();
+
+		assert_eq!(super::merkle_root::<Sha256>(leaves(0)), Sha256::hash(&[]));
+		assert_ne!(super::merkle_root::<Sha256>(leaves(1)), super::merkle_root::<Sha256>(leaves(2)));
+		assert_ne!(super::merkle_root::<Sha256>(leaves(2)), super::merkle_root::<Sha256>(leaves(3)));
+		assert_eq!(super::merkle_root::<Sha256>(leaves(3)), super::merkle_root::<Sha256>(leaves(3)));
+	}
+
+	#[test]
+	fn public_key_serde() {
+		let alice = Pair::from_name("alice").public();
+		let json = serde_json::to_string(&alice).unwrap();
+		assert_eq!(json, format!("\"{:?}\"", alice));
+		assert_eq!(serde_json::from_str::<super::ed25519::Public>(&json).unwrap(), alice);
+		assert!(serde_json::from_str::<super::ed25519::Public>("\"0x00\"").is_err());
+	}
+
+	#[test]
+	fn ed25519_sign_and_verify() {
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+
+		let signature = alice.sign(b"message");
+		assert!(signature.verify(b"message", &alice.public()));
+		assert!(!signature.verify(b"other message", &alice.public()));
+		assert!(!signature.verify(b"message", &bob.public()));
+	}
+}
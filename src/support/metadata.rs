@@ -0,0 +1,148 @@
+use crate::support::Storage;
+
+/// The description of a runtime, see `Runtime::metadata`.
+///
+/// Types are described as they are written in the pallets, e.g. `Option<T::AccountId>`, by the
+/// macros generating the metadata and by `storage_item!`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RuntimeMetadata {
+	/// Every pallet, including system, in the order they are declared in the `Runtime` struct.
+	pub pallets: Vec<PalletMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PalletMetadata {
+	/// The name of the pallet in the `Runtime` struct, and in `RuntimeCall` and `RuntimeEvent`.
+	pub name: String,
+	/// The index identifying the pallet in encoded calls and in module errors. System is 0.
+	pub index: u8,
+	pub calls: Vec<CallMetadata>,
+	pub storage: Vec<StorageEntryMetadata>,
+	pub events: Vec<EventMetadata>,
+	pub errors: Vec<ErrorMetadata>,
+}
+
+/// A callable function of a pallet, see `#[macros::call]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct CallMetadata {
+	pub name: String,
+	/// The index identifying the call in its encoding.
+	pub index: u8,
+	/// The arguments of the call, without its caller or origin.
+	pub args: Vec<FieldMetadata>,
+	pub docs: Vec<String>,
+}
+
+/// A named argument or field, and its type.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FieldMetadata {
+	pub name: String,
+	#[serde(rename = "type")]
+	pub ty: String,
+}
+
+/// An event of a pallet, see `#[macros::event]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EventMetadata {
+	pub name: String,
+	pub fields: Vec<FieldMetadata>,
+	pub docs: Vec<String>,
+}
+
+/// An error of a pallet, see `#[macros::error]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ErrorMetadata {
+	pub name: String,
+	/// The index identifying the error within the pallet, see `ModuleError`.
+	pub index: u8,
+	pub docs: Vec<String>,
+}
+
+/// A storage item, which is stored under the encoded `(pallet, name)` prefix, see
+/// `support::storage`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StorageEntryMetadata {
+	/// The name of the pallet in the storage keys, e.g. `Balances`.
+	pub pallet: String,
+	pub name: String,
+	#[serde(flatten)]
+	pub kind: StorageEntryKind,
+	/// The type of the stored values.
+	pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind")]
+pub enum StorageEntryKind {
+	Value,
+	Map { key: String },
+	DoubleMap { key1: String, key2: String },
+}
+
+/// The storage items created by `new`, e.g. by creating a pallet on top of the given storage.
+pub fn storage_metadata(new: impl FnOnce(&Storage)) -> Vec<StorageEntryMetadata> {
+	let storage = Storage::default();
+	new(&storage);
+	storage.items()
+}
+
+#[cfg(test)]
+mod test {
+	use super::{CallMetadata, ErrorMetadata, FieldMetadata, StorageEntryKind, StorageEntryMetadata};
+
+	#[test]
+	fn runtime_metadata() {
+		let metadata = crate::Runtime::metadata();
+		let names = metadata
+			.pallets
+			.iter()
+			.map(|pallet| (pallet.name.as_str(), pallet.index))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			names,
+			vec![("system", 0), ("balances", 1), ("transaction_payment", 2), ("proof_of_existence", 3), ("sudo", 4)]
+		);
+
+		let system = &metadata.pallets[0];
+		let storage_names = system.storage.iter().map(|item| item.name.as_str()).collect::<Vec<_>>();
+		assert_eq!(storage_names, vec!["BlockNumber", "BlockHash", "Nonce"]);
+
+		let balances = &metadata.pallets[1];
+		let field = |name: &str, ty: &str| FieldMetadata { name: name.to_string(), ty: ty.to_string() };
+		assert_eq!(
+			balances.calls[1],
+			CallMetadata {
+				name: "force_set_balance".to_string(),
+				index: 1,
+				args: vec![field("who", "T::AccountId"), field("amount", "T::Balance")],
+				docs: vec![
+					"Set the balance of `who` to `amount`. This can only be called by the root origin.".to_string()
+				],
+			}
+		);
+		assert_eq!(
+			balances.storage,
+			vec![StorageEntryMetadata {
+				pallet: "Balances".to_string(),
+				name: "Balances".to_string(),
+				kind: StorageEntryKind::Map { key: "T::AccountId".to_string() },
+				value: "T::Balance".to_string(),
+			}]
+		);
+		assert_eq!(balances.errors[0].name, "InsufficientBalance");
+		assert!(balances.events.iter().any(|event| event.name == "Transfer"));
+
+		let sudo = &metadata.pallets[4];
+		assert_eq!(sudo.calls[0].args, vec![field("call", "Box<T::RuntimeCall>")]);
+		assert_eq!(sudo.storage[0].kind, StorageEntryKind::Value);
+		assert_eq!(
+			sudo.errors,
+			vec![ErrorMetadata {
+				name: "RequireSudo".to_string(),
+				index: 0,
+				docs: vec!["The caller does not hold the sudo key.".to_string()]
+			}]
+		);
+		assert_eq!(metadata.pallets[2].errors, vec![]);
+	}
+}
@@ -0,0 +1,714 @@
+pub mod disk;
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, VecDeque},
+	fmt::Debug,
+	marker::PhantomData,
+	rc::Rc,
+};
+
+use crate::support::{
+	crypto,
+	metadata::{StorageEntryKind, StorageEntryMetadata},
+	Decode, Encode, Transactional,
+};
+
+/// A single storage entry, as an encoded `(key, value)` pair.
+pub type StorageEntry = (Vec<u8>, Vec<u8>);
+
+/// A key-value store holding the encoded state of the runtime.
+///
+/// Backends only ever see committed writes, see `Storage` for the transactional layers on top.
+pub trait Backend: Debug {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+	fn insert(&mut self, key: Vec<u8>, value: Vec<u8>);
+	fn remove(&mut self, key: &[u8]);
+	/// All the entries whose key starts with `prefix`, ordered by key.
+	fn entries_with_prefix(&self, prefix: &[u8]) -> Vec<StorageEntry>;
+
+	/// Auxiliary data the node keeps next to the state, e.g. its `BlockTree`. It is a separate set of
+	/// entries, which is not part of the state root.
+	fn get_aux(&self, key: &[u8]) -> Option<Vec<u8>>;
+	fn insert_aux(&mut self, key: Vec<u8>, value: Vec<u8>);
+	fn remove_aux(&mut self, key: &[u8]);
+
+	/// Make all the writes so far durable. Backends which don't persist anything have nothing to do.
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// A backend which keeps everything in memory, and loses it when dropped.
+#[derive(Default)]
+pub struct InMemoryBackend {
+	entries: BTreeMap<Vec<u8>, Vec<u8>>,
+	aux: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+// The entries themselves are shown by the storage items of each pallet.
+impl Debug for InMemoryBackend {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("InMemoryBackend")
+			.field("entries", &self.entries.len())
+			.field("aux", &self.aux.len())
+			.finish()
+	}
+}
+
+impl Backend for InMemoryBackend {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.entries.get(key).cloned()
+	}
+
+	fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.entries.insert(key, value);
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		self.entries.remove(key);
+	}
+
+	fn entries_with_prefix(&self, prefix: &[u8]) -> Vec<StorageEntry> {
+		self.entries
+			.range(prefix.to_vec()..)
+			.take_while(|(key, _)| key.starts_with(prefix))
+			.map(|(key, value)| (key.clone(), value.clone()))
+			.collect()
+	}
+
+	fn get_aux(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.aux.get(key).cloned()
+	}
+
+	fn insert_aux(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.aux.insert(key, value);
+	}
+
+	fn remove_aux(&mut self, key: &[u8]) {
+		self.aux.remove(key);
+	}
+}
+
+/// The storage of the runtime, shared by all of its pallets.
+///
+/// This is a cheap handle: clones refer to the same storage. Keys are prefixed with the name of
+/// the pallet and of the storage item, see `StorageValue`, `StorageMap` and `StorageDoubleMap`, so
+/// the whole state can be iterated and hashed without knowing about the pallets.
+///
+/// Writes made while a transaction is open are kept in an overlay on top of the backend, and only
+/// reach the backend once the outermost transaction is committed.
+#[derive(Clone)]
+pub struct Storage(Rc<RefCell<Overlay>>);
+
+struct Overlay {
+	backend: Box<dyn Backend>,
+	/// One layer per open transaction. `None` marks a key removed in that layer.
+	layers: Vec<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+	/// The storage items created on top of this storage, see `Storage::items`.
+	items: Vec<StorageEntryMetadata>,
+	/// The undo log being recorded, if any, see `Storage::start_undo_log`.
+	undo_log: Option<UndoLog>,
+	/// The retained checkpoints, oldest first, see `Storage::checkpoint`. Each of them has the undo
+	/// log of the writes made after it, until the next checkpoint.
+	checkpoints: VecDeque<(Vec<u8>, UndoLog)>,
+}
+
+impl Overlay {
+	/// Write `value` to the backend, or remove `key` if it is `None`, recording the previous value in
+	/// the undo log.
+	fn write_backend(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		let undo_logs = self
+			.undo_log
+			.iter_mut()
+			.chain(self.checkpoints.back_mut().map(|(_, undo_log)| undo_log));
+		for undo_log in undo_logs {
+			undo_log.0.entry(key.clone()).or_insert_with(|| self.backend.get(&key));
+		}
+		match value {
+			Some(value) => self.backend.insert(key, value),
+			None => self.backend.remove(&key),
+		}
+	}
+}
+
+/// The previous values of the keys written to the backend while the log was recorded, which is
+/// enough to revert these writes, see `Storage::start_undo_log`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, macros::Encode, macros::Decode)]
+pub struct UndoLog(BTreeMap<Vec<u8>, Option<Vec<u8>>>);
+
+impl Default for Storage {
+	fn default() -> Self {
+		Self::new(InMemoryBackend::default())
+	}
+}
+
+impl Storage {
+	pub fn new(backend: impl Backend + 'static) -> Self {
+		Self(Rc::new(RefCell::new(Overlay {
+			backend: Box::new(backend),
+			layers: Vec::new(),
+			items: Vec::new(),
+			undo_log: None,
+			checkpoints: VecDeque::new(),
+		})))
+	}
+
+	/// The storage items created on top of this storage so far, in the order they were created.
+	pub fn items(&self) -> Vec<StorageEntryMetadata> {
+		self.0.borrow().items.clone()
+	}
+
+	fn register(&self, pallet: &str, name: &str, kind: StorageEntryKind, value: String) {
+		let item = StorageEntryMetadata { pallet: pallet.to_string(), name: name.to_string(), kind, value };
+		self.0.borrow_mut().items.push(item);
+	}
+
+	pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let overlay = self.0.borrow();
+		// The most recent layer which knows about this key wins.
+		for layer in overlay.layers.iter().rev() {
+			if let Some(value) = layer.get(key) {
+				return value.clone();
+			}
+		}
+		overlay.backend.get(key)
+	}
+
+	pub fn insert(&self, key: Vec<u8>, value: Vec<u8>) {
+		let mut overlay = self.0.borrow_mut();
+		match overlay.layers.last_mut() {
+			Some(layer) => {
+				layer.insert(key, Some(value));
+			},
+			None => overlay.write_backend(key, Some(value)),
+		}
+	}
+
+	pub fn remove(&self, key: &[u8]) {
+		let mut overlay = self.0.borrow_mut();
+		match overlay.layers.last_mut() {
+			Some(layer) => {
+				layer.insert(key.to_vec(), None);
+			},
+			None => overlay.write_backend(key.to_vec(), None),
+		}
+	}
+
+	/// All the entries whose key starts with `prefix`, including the writes of open transactions,
+	/// ordered by key.
+	pub fn entries_with_prefix(&self, prefix: &[u8]) -> Vec<StorageEntry> {
+		let overlay = self.0.borrow();
+		let mut merged = overlay
+			.backend
+			.entries_with_prefix(prefix)
+			.into_iter()
+			.map(|(key, value)| (key, Some(value)))
+			.collect::<BTreeMap<_, _>>();
+		for layer in &overlay.layers {
+			let writes = layer.range(prefix.to_vec()..).take_while(|(key, _)| key.starts_with(prefix));
+			merged.extend(writes.map(|(key, value)| (key.clone(), value.clone())));
+		}
+		merged.into_iter().filter_map(|(key, value)| Some((key, value?))).collect()
+	}
+
+	/// The writes of the open transactions, which would reach the backend if they were all
+	/// committed, ordered by key. `None` marks a removed key.
+	pub fn overlay_changes(&self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+		let overlay = self.0.borrow();
+		let mut changes = BTreeMap::new();
+		for layer in &overlay.layers {
+			changes.extend(layer.iter().map(|(key, value)| (key.clone(), value.clone())));
+		}
+		changes.into_iter().collect()
+	}
+
+	/// All the entries of the storage, ordered by key.
+	pub fn entries(&self) -> Vec<StorageEntry> {
+		self.entries_with_prefix(&[])
+	}
+
+	/// Read the auxiliary data of the backend at `key`, see `Backend::get_aux`.
+	pub fn get_aux(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.0.borrow().backend.get_aux(key)
+	}
+
+	/// Write auxiliary data to the backend. It is not part of the state, so it is written right
+	/// away, whatever the open transactions, and it is neither undone nor reverted to a checkpoint.
+	pub fn insert_aux(&self, key: Vec<u8>, value: Vec<u8>) {
+		self.0.borrow_mut().backend.insert_aux(key, value)
+	}
+
+	pub fn remove_aux(&self, key: &[u8]) {
+		self.0.borrow_mut().backend.remove_aux(key)
+	}
+
+	/// Make the writes which reached the backend durable, including the auxiliary data, see
+	/// `Backend::flush`. Writes of open transactions are not included.
+	pub fn flush(&self) -> std::io::Result<()> {
+		self.0.borrow_mut().backend.flush()
+	}
+
+	/// Start recording the writes which reach the backend, so that they can be reverted with `undo`.
+	/// Writes of transactions are recorded when the outermost transaction is committed.
+	///
+	/// Panics if an undo log is already being recorded.
+	pub fn start_undo_log(&self) {
+		let mut overlay = self.0.borrow_mut();
+		assert!(overlay.undo_log.is_none(), "an undo log is already being recorded");
+		overlay.undo_log = Some(UndoLog::default());
+	}
+
+	/// Stop recording the undo log started with `start_undo_log`, and return it.
+	pub fn take_undo_log(&self) -> UndoLog {
+		self.0.borrow_mut().undo_log.take().expect("no undo log is being recorded")
+	}
+
+	/// Revert the writes recorded in `undo_log`. Logs must be undone in the reverse order they were
+	/// recorded in, and there must be no open transaction.
+	pub fn undo(&self, undo_log: UndoLog) {
+		let mut overlay = self.0.borrow_mut();
+		assert!(overlay.layers.is_empty(), "can't undo writes while a transaction is open");
+		for (key, value) in undo_log.0 {
+			overlay.write_backend(key, value);
+		}
+	}
+
+	/// Record a checkpoint of the storage, identified by `label`, which the storage can be reverted
+	/// to with `revert_to`. Only the last `retained` checkpoints are kept.
+	///
+	/// Checkpoints share the whole storage with each other: a checkpoint only keeps the previous
+	/// values of the keys written after it, until the next checkpoint.
+	pub fn checkpoint(&self, label: Vec<u8>, retained: usize) {
+		let mut overlay = self.0.borrow_mut();
+		assert!(overlay.layers.is_empty(), "can't record a checkpoint while a transaction is open");
+		overlay.checkpoints.push_back((label, UndoLog::default()));
+		while overlay.checkpoints.len() > retained {
+			overlay.checkpoints.pop_front();
+		}
+	}
+
+	/// The labels of the retained checkpoints, oldest first.
+	pub fn checkpoints(&self) -> Vec<Vec<u8>> {
+		self.0.borrow().checkpoints.iter().map(|(label, _)| label.clone()).collect()
+	}
+
+	/// Revert the storage to the last checkpoint with `label`, which stays retained, while the
+	/// checkpoints after it are dropped. Returns `false` if no such checkpoint is retained.
+	pub fn revert_to(&self, label: &[u8]) -> bool {
+		let mut overlay = self.0.borrow_mut();
+		assert!(overlay.layers.is_empty(), "can't revert to a checkpoint while a transaction is open");
+		let Some(index) = overlay.checkpoints.iter().rposition(|(checkpoint, _)| checkpoint == label) else {
+			return false;
+		};
+		// The checkpoints are put aside, so that these writes are not recorded as writes made after
+		// the remaining checkpoints.
+		let mut checkpoints = std::mem::take(&mut overlay.checkpoints);
+		for (_, undo_log) in checkpoints.drain(index..).rev() {
+			for (key, value) in undo_log.0 {
+				overlay.write_backend(key, value);
+			}
+		}
+		checkpoints.push_back((label.to_vec(), UndoLog::default()));
+		overlay.checkpoints = checkpoints;
+		true
+	}
+
+	/// The root of the whole storage, see `state_root`.
+	pub fn root<Hashing>(&self) -> Hashing::Output
+	where
+		Hashing: crypto::Hash,
+		Hashing::Output: Encode,
+	{
+		state_root::<Hashing>(self.entries())
+	}
+}
+
+impl Transactional for Storage {
+	fn start_transaction(&mut self) {
+		self.0.borrow_mut().layers.push(BTreeMap::new());
+	}
+
+	fn commit_transaction(&mut self) {
+		let mut overlay = self.0.borrow_mut();
+		let layer = overlay.layers.pop().expect("no open transaction to commit");
+		for (key, value) in layer {
+			match (overlay.layers.last_mut(), value) {
+				(Some(parent), value) => {
+					parent.insert(key, value);
+				},
+				(None, value) => overlay.write_backend(key, value),
+			}
+		}
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.0.borrow_mut().layers.pop().expect("no open transaction to rollback");
+	}
+}
+
+impl Debug for Storage {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let overlay = self.0.borrow();
+		f.debug_struct("Storage")
+			.field("backend", &overlay.backend)
+			.field("open_transactions", &overlay.layers.len())
+			.finish()
+	}
+}
+
+/// Decode a value read from the storage. Storage items only ever write valid encodings, so a
+/// failure means the storage is corrupted.
+fn decode_stored<V: Decode>(bytes: &[u8]) -> V {
+	V::decode_all(bytes).expect("storage items only hold valid encodings")
+}
+
+/// Create the storage item `$item` of `$pallet`, of the given type, e.g.
+/// `storage_item!(StorageMap<T::AccountId, T::Balance>, storage, "Balances", "Balances")`. The
+/// metadata of the item describes its types as they are written here.
+macro_rules! storage_item {
+	($kind:ident < $( $ty:ty ),+ >, $storage:expr, $pallet:expr, $item:expr) => {
+		$crate::support::storage::$kind::<$( $ty ),+>::new($storage, $pallet, $item, [$( stringify!($ty) ),+])
+	};
+}
+pub(crate) use storage_item;
+
+/// A single value in the storage, stored at the key `(pallet, item)`.
+pub struct StorageValue<V> {
+	storage: Storage,
+	key: Vec<u8>,
+	_value: PhantomData<V>,
+}
+
+impl<V: Encode + Decode> StorageValue<V> {
+	/// Create the item, whose value has the type named `value`, see `storage_item!`.
+	pub fn new(storage: &Storage, pallet: &str, item: &str, [value]: [&str; 1]) -> Self {
+		storage.register(pallet, item, StorageEntryKind::Value, value.to_string());
+		Self { storage: storage.clone(), key: (pallet, item).encode(), _value: PhantomData }
+	}
+
+	/// The value, or `None` if it was never set or has been killed.
+	pub fn get(&self) -> Option<V> {
+		self.storage.get(&self.key).map(|bytes| decode_stored(&bytes))
+	}
+
+	pub fn set(&mut self, value: V) {
+		self.storage.insert(self.key.clone(), value.encode());
+	}
+
+	/// Remove the value from the storage.
+	pub fn kill(&mut self) {
+		self.storage.remove(&self.key);
+	}
+}
+
+impl<V: Encode + Decode + Debug> Debug for StorageValue<V> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		self.get().fmt(f)
+	}
+}
+
+/// A map in the storage, where the value of `key` is stored at the key `(pallet, item, key)`.
+pub struct StorageMap<K, V> {
+	storage: Storage,
+	prefix: Vec<u8>,
+	_entries: PhantomData<(K, V)>,
+}
+
+impl<K: Encode + Decode, V: Encode + Decode> StorageMap<K, V> {
+	/// Create the item, whose keys and values have the types named `key` and `value`, see
+	/// `storage_item!`.
+	pub fn new(storage: &Storage, pallet: &str, item: &str, [key, value]: [&str; 2]) -> Self {
+		storage.register(pallet, item, StorageEntryKind::Map { key: key.to_string() }, value.to_string());
+		Self { storage: storage.clone(), prefix: (pallet, item).encode(), _entries: PhantomData }
+	}
+
+	fn storage_key(&self, key: &K) -> Vec<u8> {
+		let mut storage_key = self.prefix.clone();
+		key.encode_to(&mut storage_key);
+		storage_key
+	}
+
+	pub fn get(&self, key: &K) -> Option<V> {
+		self.storage.get(&self.storage_key(key)).map(|bytes| decode_stored(&bytes))
+	}
+
+	pub fn contains_key(&self, key: &K) -> bool {
+		self.storage.get(&self.storage_key(key)).is_some()
+	}
+
+	pub fn insert(&mut self, key: K, value: V) {
+		self.storage.insert(self.storage_key(&key), value.encode());
+	}
+
+	pub fn remove(&mut self, key: K) {
+		self.storage.remove(&self.storage_key(&key));
+	}
+
+	/// All the entries of the map, ordered by their encoded key.
+	pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
+		let prefix_len = self.prefix.len();
+		self.storage
+			.entries_with_prefix(&self.prefix)
+			.into_iter()
+			.map(move |(key, value)| (decode_stored(&key[prefix_len..]), decode_stored(&value)))
+	}
+}
+
+impl<K: Encode + Decode + Debug, V: Encode + Decode + Debug> Debug for StorageMap<K, V> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_map().entries(self.iter()).finish()
+	}
+}
+
+/// A map with two keys in the storage, where the value of `(key1, key2)` is stored at the key
+/// `(pallet, item, key1, key2)`. All the entries sharing `key1` can be iterated together.
+#[allow(dead_code)]
+pub struct StorageDoubleMap<K1, K2, V> {
+	storage: Storage,
+	prefix: Vec<u8>,
+	_entries: PhantomData<(K1, K2, V)>,
+}
+
+#[allow(dead_code)]
+impl<K1: Encode + Decode, K2: Encode + Decode, V: Encode + Decode> StorageDoubleMap<K1, K2, V> {
+	/// Create the item, whose keys and values have the types named `key1`, `key2` and `value`, see
+	/// `storage_item!`.
+	pub fn new(storage: &Storage, pallet: &str, item: &str, [key1, key2, value]: [&str; 3]) -> Self {
+		let kind = StorageEntryKind::DoubleMap { key1: key1.to_string(), key2: key2.to_string() };
+		storage.register(pallet, item, kind, value.to_string());
+		Self { storage: storage.clone(), prefix: (pallet, item).encode(), _entries: PhantomData }
+	}
+
+	fn prefix_of(&self, key1: &K1) -> Vec<u8> {
+		let mut prefix = self.prefix.clone();
+		key1.encode_to(&mut prefix);
+		prefix
+	}
+
+	fn storage_key(&self, key1: &K1, key2: &K2) -> Vec<u8> {
+		let mut storage_key = self.prefix_of(key1);
+		key2.encode_to(&mut storage_key);
+		storage_key
+	}
+
+	pub fn get(&self, key1: &K1, key2: &K2) -> Option<V> {
+		self.storage
+			.get(&self.storage_key(key1, key2))
+			.map(|bytes| decode_stored(&bytes))
+	}
+
+	pub fn contains_key(&self, key1: &K1, key2: &K2) -> bool {
+		self.storage.get(&self.storage_key(key1, key2)).is_some()
+	}
+
+	pub fn insert(&mut self, key1: K1, key2: K2, value: V) {
+		self.storage.insert(self.storage_key(&key1, &key2), value.encode());
+	}
+
+	pub fn remove(&mut self, key1: K1, key2: K2) {
+		self.storage.remove(&self.storage_key(&key1, &key2));
+	}
+
+	/// All the entries under `key1`, ordered by their encoded second key.
+	pub fn iter_prefix(&self, key1: &K1) -> impl Iterator<Item = (K2, V)> {
+		let prefix = self.prefix_of(key1);
+		let prefix_len = prefix.len();
+		self.storage
+			.entries_with_prefix(&prefix)
+			.into_iter()
+			.map(move |(key, value)| (decode_stored(&key[prefix_len..]), decode_stored(&value)))
+	}
+
+	/// All the entries of the map, ordered by their encoded keys.
+	pub fn iter(&self) -> impl Iterator<Item = (K1, K2, V)> {
+		let prefix_len = self.prefix.len();
+		self.storage
+			.entries_with_prefix(&self.prefix)
+			.into_iter()
+			.map(move |(key, value)| {
+				let (key1, key2) = decode_stored(&key[prefix_len..]);
+				(key1, key2, decode_stored(&value))
+			})
+	}
+}
+
+impl<K1, K2, V> Debug for StorageDoubleMap<K1, K2, V>
+where
+	K1: Encode + Decode + Debug,
+	K2: Encode + Decode + Debug,
+	V: Encode + Decode + Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_map()
+			.entries(self.iter().map(|(key1, key2, value)| ((key1, key2), value)))
+			.finish()
+	}
+}
+
+/// Compute the root of a Merkle tree over the storage `entries`.
+///
+/// The entries are sorted by key first, so the root only depends on the contents of the storage.
+pub fn state_root<Hashing>(mut entries: Vec<StorageEntry>) -> Hashing::Output
+where
+	Hashing: crypto::Hash,
+	Hashing::Output: Encode,
+{
+	entries.sort();
+	crypto::merkle_root::<Hashing>(entries.iter().map(|entry| entry.encode()))
+}
+
+#[cfg(test)]
+mod test {
+	use super::{state_root, Storage};
+	use crate::support::{crypto::Sha256, with_transaction, Transactional};
+
+	#[test]
+	fn state_root_only_depends_on_contents() {
+		let storage = Storage::default();
+		let mut map = storage_item!(StorageMap<u32, u32>, &storage, "Pallet", "Map");
+		map.insert(1, 1);
+		let mut value = storage_item!(StorageValue<u32>, &storage, "Pallet", "Value");
+		value.set(5);
+
+		let mut entries = storage.entries();
+		let root = state_root::<Sha256>(entries.clone());
+		assert_eq!(storage.root::<Sha256>(), root);
+
+		// The order of the entries does not matter.
+		entries.reverse();
+		assert_eq!(state_root::<Sha256>(entries), root);
+
+		// But any change to the contents does.
+		map.insert(2, 2);
+		assert_ne!(storage.root::<Sha256>(), root);
+	}
+
+	#[test]
+	fn typed_storage_items() {
+		let storage = Storage::default();
+		let mut value = storage_item!(StorageValue<u32>, &storage, "Pallet", "Value");
+		let mut map = storage_item!(StorageMap<String, u32>, &storage, "Pallet", "Map");
+		let mut double_map = storage_item!(StorageDoubleMap<String, u32, bool>, &storage, "Pallet", "DoubleMap");
+		let other_map = storage_item!(StorageMap<String, u32>, &storage, "Other", "Map");
+
+		assert_eq!(value.get(), None);
+		value.set(1);
+		assert_eq!(value.get(), Some(1));
+		value.kill();
+		assert_eq!(value.get(), None);
+
+		map.insert("bob".to_string(), 2);
+		map.insert("alice".to_string(), 1);
+		assert_eq!(map.get(&"alice".to_string()), Some(1));
+		assert!(!map.contains_key(&"charlie".to_string()));
+		assert_eq!(map.iter().collect::<Vec<_>>(), vec![("bob".to_string(), 2), ("alice".to_string(), 1)]);
+		// Items of other pallets are kept apart, even with the same name.
+		assert_eq!(other_map.iter().count(), 0);
+
+		double_map.insert("alice".to_string(), 1, true);
+		double_map.insert("alice".to_string(), 2, false);
+		double_map.insert("bob".to_string(), 1, true);
+		assert_eq!(double_map.get(&"alice".to_string(), &2), Some(false));
+		assert_eq!(double_map.iter_prefix(&"alice".to_string()).collect::<Vec<_>>(), vec![(1, true), (2, false)]);
+		double_map.remove("alice".to_string(), 1);
+		assert_eq!(double_map.iter().count(), 2);
+
+		// Every item lives in the same storage.
+		assert_eq!(storage.entries().len(), 4);
+	}
+
+	#[test]
+	fn commit_and_rollback() {
+		let mut storage = Storage::default();
+		let mut map = storage_item!(StorageMap<String, u32>, &storage, "Pallet", "Map");
+		let alice = || "alice".to_string();
+		let bob = || "bob".to_string();
+		map.insert(alice(), 1);
+
+		storage.start_transaction();
+		map.insert(alice(), 2);
+		map.insert(bob(), 1);
+		assert_eq!(map.get(&alice()), Some(2));
+		storage.rollback_transaction();
+
+		assert_eq!(map.get(&alice()), Some(1));
+		assert_eq!(map.get(&bob()), None);
+
+		storage.start_transaction();
+		map.remove(alice());
+		map.insert(bob(), 1);
+		storage.commit_transaction();
+
+		assert_eq!(map.get(&alice()), None);
+		assert_eq!(map.get(&bob()), Some(1));
+
+		storage.start_transaction();
+		map.insert(alice(), 2);
+		map.remove(bob());
+		assert_eq!(map.iter().collect::<Vec<_>>(), vec![(alice(), 2)]);
+		storage.rollback_transaction();
+		assert_eq!(map.iter().collect::<Vec<_>>(), vec![(bob(), 1)]);
+	}
+
+	#[test]
+	fn nested_transactions() {
+		let mut storage = Storage::default();
+		let mut value = storage_item!(StorageValue<u32>, &storage, "Pallet", "Value");
+
+		let res: Result<(), ()> = with_transaction(&mut storage, |storage| {
+			value.set(1);
+
+			// The inner failure only discards the inner writes.
+			let inner: Result<(), ()> = with_transaction(storage, |_| {
+				value.set(2);
+				Err(())
+			});
+			assert_eq!(inner, Err(()));
+			assert_eq!(value.get(), Some(1));
+			Ok(())
+		});
+		assert_eq!(res, Ok(()));
+		assert_eq!(value.get(), Some(1));
+
+		// An outer failure discards everything, including committed inner layers.
+		let res: Result<(), ()> = with_transaction(&mut storage, |storage| {
+			let _: Result<(), ()> = with_transaction(storage, |_| {
+				value.kill();
+				Ok(())
+			});
+			Err(())
+		});
+		assert_eq!(res, Err(()));
+		assert_eq!(value.get(), Some(1));
+	}
+
+	#[test]
+	fn undo_log() {
+		let mut storage = Storage::default();
+		let mut map = storage_item!(StorageMap<u32, u32>, &storage, "Pallet", "Map");
+		map.insert(1, 1);
+		map.insert(2, 2);
+		let root = storage.root::<Sha256>();
+
+		storage.start_undo_log();
+		map.insert(1, 10);
+		map.insert(1, 100);
+		map.remove(2);
+		let _: Result<(), ()> = with_transaction(&mut storage, |_| {
+			map.insert(3, 3);
+			Ok(())
+		});
+		// Rolled back writes never reach the backend, so there is nothing to undo.
+		let _: Result<(), ()> = with_transaction(&mut storage, |_| {
+			map.insert(4, 4);
+			Err(())
+		});
+		let undo_log = storage.take_undo_log();
+		assert_eq!(map.iter().collect::<Vec<_>>(), vec![(1, 100), (3, 3)]);
+
+		storage.undo(undo_log);
+		assert_eq!(map.iter().collect::<Vec<_>>(), vec![(1, 1), (2, 2)]);
+		assert_eq!(storage.root::<Sha256>(), root);
+	}
+}
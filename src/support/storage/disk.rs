@@ -0,0 +1,362 @@
+use std::{
+	collections::BTreeMap,
+	fs::{File, OpenOptions},
+	io::{Read, Write},
+	path::{Path, PathBuf},
+};
+
+use super::{Backend, StorageEntry};
+use crate::support::{
+	crypto::{Hash, Sha256},
+	Decode, Encode,
+};
+
+/// A write to the log: the new value of a key, or `None` if the key was removed.
+type LogWrite = (Vec<u8>, Option<Vec<u8>>);
+
+/// The writes of a batch, to the entries and to the auxiliary data, see `Backend::get_aux`.
+type Batch = (Vec<LogWrite>, Vec<LogWrite>);
+
+/// The log is only compacted once it is larger than this, so small logs are never rewritten.
+const MIN_COMPACTION_LEN: u64 = 64 * 1024;
+
+/// A backend which persists the storage in an append-only log on disk.
+///
+/// All the entries are kept in memory as well, and the log is replayed when it is opened. Writes
+/// are buffered until `flush`, which appends them to the log as a single batch: after a crash, the
+/// storage comes back as it was after one of the flushes, and so does the auxiliary data, which is
+/// written in the same batches. A batch is only replayed if it was completely written, which is
+/// checked with its length, and if it still matches its checksum.
+///
+/// Once the log is more than twice the size of the entries it holds, it is compacted into a single
+/// batch, which is written to a new file that then replaces the log.
+pub struct DiskBackend {
+	path: PathBuf,
+	log: File,
+	/// The size of the log, up to the end of the last complete batch.
+	log_len: u64,
+	entries: BTreeMap<Vec<u8>, Vec<u8>>,
+	aux: BTreeMap<Vec<u8>, Vec<u8>>,
+	/// The writes since the last flush.
+	pending: Batch,
+}
+
+impl DiskBackend {
+	/// Open the log at `path`, creating it if it does not exist.
+	///
+	/// An incomplete batch at the end of the log, e.g. after a crash in the middle of a flush, is
+	/// discarded. A complete batch which does not match its checksum means the log is corrupted,
+	/// which is an error rather than a reason to drop it and everything after it.
+	pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let mut log = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+		let mut bytes = Vec::new();
+		log.read_to_end(&mut bytes)?;
+
+		let (mut entries, mut aux) = (BTreeMap::new(), BTreeMap::new());
+		let mut input = bytes.as_slice();
+		while let Some((writes, aux_writes)) = read_batch(&mut input)? {
+			for (key, value) in writes {
+				apply_write(&mut entries, key, value);
+			}
+			for (key, value) in aux_writes {
+				apply_write(&mut aux, key, value);
+			}
+		}
+		let log_len = (bytes.len() - input.len()) as u64;
+		if !input.is_empty() {
+			log.set_len(log_len)?;
+		}
+
+		Ok(Self { path, log, log_len, entries, aux, pending: Default::default() })
+	}
+
+	/// Rewrite the log as a single batch holding the current entries and auxiliary data. This is
+	/// only done right after a flush, so that they are exactly the flushed state.
+	fn compact(&mut self) -> std::io::Result<()> {
+		debug_assert!(self.pending == Batch::default(), "only the flushed state is compacted");
+		let writes = |map: &BTreeMap<Vec<u8>, Vec<u8>>| {
+			map.iter()
+				.map(|(key, value)| (key.clone(), Some(value.clone())))
+				.collect::<Vec<_>>()
+		};
+		let bytes = encode_batch(&(writes(&self.entries), writes(&self.aux)));
+
+		let mut compacted_path = self.path.clone().into_os_string();
+		compacted_path.push(".compact");
+		let mut compacted = File::create(&compacted_path)?;
+		compacted.write_all(&bytes)?;
+		compacted.sync_all()?;
+		std::fs::rename(&compacted_path, &self.path)?;
+		// The rename is only durable once the directory holding the log is synced.
+		let dir = match self.path.parent() {
+			Some(dir) if !dir.as_os_str().is_empty() => dir,
+			_ => Path::new("."),
+		};
+		File::open(dir)?.sync_all()?;
+
+		self.log = OpenOptions::new().read(true).append(true).open(&self.path)?;
+		self.log_len = bytes.len() as u64;
+		Ok(())
+	}
+
+	/// The size the log would have once compacted.
+	fn compacted_len(&self) -> u64 {
+		fn writes(map: &BTreeMap<Vec<u8>, Vec<u8>>) -> Vec<(&Vec<u8>, Option<&Vec<u8>>)> {
+			map.iter().map(|(key, value)| (key, Some(value))).collect()
+		}
+		((writes(&self.entries), writes(&self.aux)).encode().len() + BATCH_HEADER_LEN) as u64
+	}
+}
+
+/// Replay a write of the log on `map`.
+fn apply_write(map: &mut BTreeMap<Vec<u8>, Vec<u8>>, key: Vec<u8>, value: Option<Vec<u8>>) {
+	match value {
+		Some(value) => map.insert(key, value),
+		None => map.remove(&key),
+	};
+}
+
+/// Every batch starts with the length of its contents, and their checksum.
+const BATCH_HEADER_LEN: usize = 4 + 32;
+
+fn encode_batch(batch: &Batch) -> Vec<u8> {
+	let contents = batch.encode();
+	let mut bytes = Vec::with_capacity(BATCH_HEADER_LEN + contents.len());
+	(contents.len() as u32).encode_to(&mut bytes);
+	Sha256::hash(&contents).encode_to(&mut bytes);
+	bytes.extend_from_slice(&contents);
+	bytes
+}
+
+/// Read the next batch of `input`, unless it was not completely written.
+fn read_batch(input: &mut &[u8]) -> std::io::Result<Option<Batch>> {
+	let mut header = *input;
+	let (Ok(len), Ok(checksum)) = (u32::decode(&mut header), <[u8; 32]>::decode(&mut header)) else {
+		return Ok(None);
+	};
+	if header.len() < len as usize {
+		return Ok(None);
+	}
+	let (contents, rest) = header.split_at(len as usize);
+	let corrupted = |reason| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+	if Sha256::hash(contents).0 != checksum {
+		return Err(corrupted("a batch of the log does not match its checksum"));
+	}
+	let batch = Batch::decode_all(contents).map_err(|_| corrupted("a batch of the log cannot be decoded"))?;
+	*input = rest;
+	Ok(Some(batch))
+}
+
+impl Backend for DiskBackend {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.entries.get(key).cloned()
+	}
+
+	fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.entries.insert(key.clone(), value.clone());
+		self.pending.0.push((key, Some(value)));
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		self.entries.remove(key);
+		self.pending.0.push((key.to_vec(), None));
+	}
+
+	fn entries_with_prefix(&self, prefix: &[u8]) -> Vec<StorageEntry> {
+		self.entries
+			.range(prefix.to_vec()..)
+			.take_while(|(key, _)| key.starts_with(prefix))
+			.map(|(key, value)| (key.clone(), value.clone()))
+			.collect()
+	}
+
+	fn get_aux(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.aux.get(key).cloned()
+	}
+
+	fn insert_aux(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.aux.insert(key.clone(), value.clone());
+		self.pending.1.push((key, Some(value)));
+	}
+
+	fn remove_aux(&mut self, key: &[u8]) {
+		self.aux.remove(key);
+		self.pending.1.push((key.to_vec(), None));
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		if self.pending == Batch::default() {
+			return Ok(());
+		}
+		let bytes = encode_batch(&self.pending);
+		if let Err(error) = self.log.write_all(&bytes).and_then(|()| self.log.sync_data()) {
+			// Drop whatever part of the batch was written, so that the next flush appends to the
+			// last complete batch. The pending writes are kept for it.
+			let _ = self.log.set_len(self.log_len);
+			return Err(error);
+		}
+		self.log_len += bytes.len() as u64;
+		self.pending = Batch::default();
+
+		if self.log_len > MIN_COMPACTION_LEN && self.log_len > 2 * self.compacted_len() {
+			self.compact()?;
+		}
+		Ok(())
+	}
+}
+
+impl std::fmt::Debug for DiskBackend {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("DiskBackend")
+			.field("path", &self.path)
+			.field("log_len", &self.log_len)
+			.field("entries", &self.entries.len())
+			.field("aux", &self.aux.len())
+			.field("pending", &(self.pending.0.len() + self.pending.1.len()))
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::DiskBackend;
+	use crate::{
+		balances,
+		block_builder::BlockBuilder,
+		chain_spec::ChainSpec,
+		support::{crypto::ed25519::Pair, storage::Backend, Storage},
+		Runtime, RuntimeCall,
+	};
+
+	/// A path in the temporary directory which is unique to this test run.
+	fn temp_path(name: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("rsm-{}-{}.log", std::process::id(), name));
+		let _ = std::fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn log_is_replayed_until_an_incomplete_batch() {
+		let path = temp_path("replay");
+		let mut backend = DiskBackend::open(&path).unwrap();
+		backend.insert(b"alice".to_vec(), b"1".to_vec());
+		backend.insert(b"bob".to_vec(), b"2".to_vec());
+		backend.insert_aux(b"tree".to_vec(), b"1".to_vec());
+		backend.flush().unwrap();
+		backend.remove(b"alice");
+		backend.flush().unwrap();
+		// Writes which are not flushed are lost.
+		backend.insert(b"charlie".to_vec(), b"3".to_vec());
+		backend.remove_aux(b"tree");
+		let flushed_len = backend.log_len;
+		drop(backend);
+
+		// Simulate a crash in the middle of a flush.
+		let mut bytes = std::fs::read(&path).unwrap();
+		bytes.extend_from_slice(&[9, 0, 0, 0, 1, 2]);
+		std::fs::write(&path, bytes).unwrap();
+
+		let backend = DiskBackend::open(&path).unwrap();
+		assert_eq!(backend.get(b"alice"), None);
+		assert_eq!(backend.get(b"bob"), Some(b"2".to_vec()));
+		assert_eq!(backend.get(b"charlie"), None);
+		// The auxiliary data is flushed with the entries, and is not one of them.
+		assert_eq!(backend.get_aux(b"tree"), Some(b"1".to_vec()));
+		assert_eq!(backend.get(b"tree"), None);
+		// The incomplete batch is dropped from the log.
+		assert_eq!(backend.log_len, flushed_len);
+		assert_eq!(std::fs::metadata(&path).unwrap().len(), flushed_len);
+
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn corrupted_log_is_not_opened() {
+		let path = temp_path("corruption");
+		let mut backend = DiskBackend::open(&path).unwrap();
+		backend.insert(b"alice".to_vec(), b"1".to_vec());
+		backend.flush().unwrap();
+		backend.insert(b"bob".to_vec(), b"2".to_vec());
+		backend.flush().unwrap();
+		drop(backend);
+
+		// Flip a byte of the first batch, which is complete.
+		let mut bytes = std::fs::read(&path).unwrap();
+		bytes[super::BATCH_HEADER_LEN] ^= 1;
+		std::fs::write(&path, &bytes).unwrap();
+
+		let error = DiskBackend::open(&path).unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+		// The log is left as it was, rather than truncated to the corrupted batch.
+		assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn log_is_compacted() {
+		let path = temp_path("compaction");
+		let mut backend = DiskBackend::open(&path).unwrap();
+		backend.insert_aux(b"tree".to_vec(), b"1".to_vec());
+		for i in 0..100u32 {
+			backend.insert(b"key".to_vec(), vec![i as u8; 1024]);
+			backend.flush().unwrap();
+		}
+		// Without compaction the log would hold every overwritten value.
+		assert!(backend.log_len < super::MIN_COMPACTION_LEN);
+		assert_eq!(std::fs::metadata(&path).unwrap().len(), backend.log_len);
+		drop(backend);
+
+		let mut backend = DiskBackend::open(&path).unwrap();
+		assert_eq!(backend.get(b"key"), Some(vec![99; 1024]));
+		backend.compact().unwrap();
+		assert_eq!(backend.log_len, backend.compacted_len());
+		let backend = DiskBackend::open(&path).unwrap();
+		assert_eq!(backend.get(b"key"), Some(vec![99; 1024]));
+		assert_eq!(backend.get_aux(b"tree"), Some(b"1".to_vec()));
+
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn node_restarts_from_the_last_imported_block() {
+		let path = temp_path("restart");
+		let alice = Pair::from_name("alice");
+		let bob = Pair::from_name("bob");
+		let transfer = |nonce| {
+			let call = RuntimeCall::balances(balances::Call::transfer { to: bob.public(), amount: 1_000 });
+			crate::sign(&alice, nonce, call)
+		};
+
+		let mut runtime = ChainSpec::development()
+			.genesis
+			.build_with_storage(Storage::new(DiskBackend::open(&path).unwrap()));
+		let mut builder = BlockBuilder::new(&mut runtime, None);
+		assert_eq!(builder.push(transfer(0)), Ok(Ok(())));
+		let block = builder.build();
+		runtime.execute_block(block).unwrap();
+		runtime.system.storage().flush().unwrap();
+		let (root, parent_hash) = (runtime.state_root(), runtime.system.parent_hash());
+		drop(runtime);
+
+		let mut runtime = Runtime::with_storage(Storage::new(DiskBackend::open(&path).unwrap()));
+		assert_eq!(runtime.state_root(), root);
+		assert_eq!(runtime.system.parent_hash(), parent_hash);
+		assert_eq!(runtime.system.block_number(), 1);
+		assert_eq!(runtime.system.account_nonce(&alice.public()), 1);
+		assert_eq!(runtime.balances.balance(&bob.public()), 1_000);
+		assert_eq!(runtime.proof_of_existence.get_claim(&"genesis".to_string()), Some(alice.public()));
+
+		// The restarted node carries on with the chain.
+		let mut builder = BlockBuilder::new(&mut runtime, None);
+		assert_eq!(builder.push(transfer(1)), Ok(Ok(())));
+		let block = builder.build();
+		assert!(runtime.execute_block(block).is_ok());
+		assert_eq!(runtime.balances.balance(&bob.public()), 2_000);
+
+		std::fs::remove_file(path).unwrap();
+	}
+}
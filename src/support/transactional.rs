@@ -0,0 +1,109 @@
+/// Something whose writes can be grouped into (nested) storage transactions.
+///
+/// Every call to `start_transaction` must be matched by exactly one call to either
+/// `commit_transaction` or `rollback_transaction`.
+pub trait Transactional {
+	/// Open a new transactional layer. All writes from now on go into this layer.
+	fn start_transaction(&mut self);
+	/// Merge the writes of the top-most layer into the layer below it.
+	fn commit_transaction(&mut self);
+	/// Discard all writes made since the matching `start_transaction`.
+	fn rollback_transaction(&mut self);
+}
+
+/// Execute `f` inside a new storage transaction on `state`.
+///
+/// If `f` returns `Ok` the transaction is committed, otherwise every write made by `f` is rolled
+/// back. Transactions can be nested: a rollback only discards the writes of its own layer.
+pub fn with_transaction<S, R, E>(state: &mut S, f: impl FnOnce(&mut S) -> Result<R, E>) -> Result<R, E>
+where
+	S: Transactional,
+{
+	state.start_transaction();
+	let result = f(state);
+	match result {
+		Ok(_) => state.commit_transaction(),
+		Err(_) => state.rollback_transaction(),
+	}
+	result
+}
+
+/// A single value which supports nested storage transactions.
+///
+/// Starting a transaction saves a copy of the current value, which is restored on rollback.
+#[derive(Debug)]
+pub struct TransactionalValue<V> {
+	value: V,
+	/// The value at the start of each open transaction.
+	saved: Vec<V>,
+}
+
+impl<V: Clone> TransactionalValue<V> {
+	pub fn new(value: V) -> Self {
+		Self { value, saved: Vec::new() }
+	}
+
+	pub fn get(&self) -> &V {
+		&self.value
+	}
+
+	pub fn get_mut(&mut self) -> &mut V {
+		&mut self.value
+	}
+
+	pub fn set(&mut self, value: V) {
+		self.value = value;
+	}
+}
+
+impl<V: Clone> Transactional for TransactionalValue<V> {
+	fn start_transaction(&mut self) {
+		self.saved.push(self.value.clone());
+	}
+
+	fn commit_transaction(&mut self) {
+		self.saved.pop().expect("no open transaction to commit");
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.value = self.saved.pop().expect("no open transaction to rollback");
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{with_transaction, Transactional, TransactionalValue};
+
+	#[test]
+	fn transactional_value() {
+		let mut value = TransactionalValue::new(1);
+
+		value.start_transaction();
+		value.set(2);
+		value.start_transaction();
+		value.set(3);
+		value.rollback_transaction();
+		assert_eq!(value.get(), &2);
+		value.commit_transaction();
+		assert_eq!(value.get(), &2);
+	}
+
+	#[test]
+	fn with_transaction_rolls_back_errors() {
+		let mut value = TransactionalValue::new(1);
+
+		let res: Result<(), ()> = with_transaction(&mut value, |value| {
+			value.set(2);
+			Err(())
+		});
+		assert_eq!(res, Err(()));
+		assert_eq!(value.get(), &1);
+
+		let res: Result<(), ()> = with_transaction(&mut value, |value| {
+			value.set(3);
+			Ok(())
+		});
+		assert_eq!(res, Ok(()));
+		assert_eq!(value.get(), &3);
+	}
+}
@@ -1,57 +1,624 @@
-use std::{collections::BTreeMap, ops::AddAssign};
+use std::{fmt::Debug, ops::AddAssign};
 
-use num::{One, Zero};
+use num::{CheckedSub, One, Zero};
+
+use crate::support::{
+	storage::storage_item, BlockReceipt, BuildGenesisConfig, Decode, DispatchError, Encode, ExtrinsicReceipt, Hash,
+	Header, Hooks, InvalidTransaction, Storage, StorageMap, StorageValue, Transactional, TransactionalValue, Verify,
+	Weight,
+};
+
+/// The point in the execution of a block at which an event was deposited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+	/// Before any extrinsic is applied.
+	Initialization,
+	/// While applying the extrinsic at this index in the block.
+	ApplyExtrinsic(u32),
+	/// After all extrinsics have been applied.
+	Finalization,
+}
+
+/// An event, together with the phase of the block in which it was deposited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRecord<E> {
+	pub phase: Phase,
+	pub event: E,
+}
 
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-	block_number: T::BlockNumber,
-	nonce: BTreeMap<T::AccountId, T::Nonce>,
+	/// The storage shared by all the pallets of the runtime. Every runtime has a system pallet, so
+	/// its transactions cover the storage items of the other pallets too.
+	storage: Storage,
+	block_number: StorageValue<T::BlockNumber>,
+	/// The hashes of the most recent blocks, including the genesis block while it is recent.
+	block_hash: StorageMap<T::BlockNumber, T::Hash>,
+	nonce: StorageMap<T::AccountId, T::Nonce>,
+	/// The events deposited in the current block. These are reset at the start of every block, and
+	/// are not part of the storage.
+	events: TransactionalValue<Vec<EventRecord<T::RuntimeEvent>>>,
+	/// The current phase of the block.
+	phase: TransactionalValue<Phase>,
+	/// The number of extrinsics applied so far in the current block.
+	extrinsic_count: TransactionalValue<u32>,
+	/// The weight consumed so far in the current block.
+	block_weight: TransactionalValue<Weight>,
+	/// The author of the current block. Like the events, this is not part of the storage.
+	author: TransactionalValue<Option<T::AccountId>>,
+}
+
+/// Who a call is dispatched on behalf of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawOrigin<AccountId> {
+	/// The system itself, which is allowed to make privileged calls.
+	Root,
+	/// The account which signed the extrinsic.
+	Signed(AccountId),
+	/// Nobody in particular, e.g. an unsigned call.
+	None,
+}
+
+/// Ensure that `origin` is a signed origin, returning the account which signed.
+pub fn ensure_signed<T: Config>(origin: T::RuntimeOrigin) -> Result<T::AccountId, DispatchError> {
+	match origin.into() {
+		RawOrigin::Signed(who) => Ok(who),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
+
+/// Ensure that `origin` is the root origin.
+pub fn ensure_root<T: Config>(origin: T::RuntimeOrigin) -> Result<(), DispatchError> {
+	match origin.into() {
+		RawOrigin::Root => Ok(()),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
+
+/// The initial state of the system pallet.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(
+	bound(serialize = "T::BlockNumber: serde::Serialize", deserialize = "T::BlockNumber: serde::Deserialize<'de>"),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The number of the genesis block. The first imported block has the next number.
+	pub block_number: T::BlockNumber,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { block_number: T::BlockNumber::zero() }
+	}
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+	fn build(&self, pallet: &mut Pallet<T>) {
+		pallet.block_hash.remove(pallet.block_number());
+		pallet.block_number.set(self.block_number);
+		pallet
+			.block_hash
+			.insert(self.block_number, Pallet::<T>::genesis_header(self.block_number).hash::<T::Hashing>());
+	}
 }
 
 pub trait Config {
-	type AccountId: Ord + Clone;
-	type Nonce: Zero + One + Copy;
-	type BlockNumber: Zero + One + Copy + AddAssign;
+	/// Accounts are identified by the public key of whoever signs their extrinsics.
+	type AccountId: Ord + Clone + Encode + Decode;
+	/// The signature scheme used to sign extrinsics.
+	type Signature: Verify<Signer = Self::AccountId>;
+	type Nonce: Zero + One + Copy + Ord + Encode + Decode;
+	type BlockNumber: Zero + One + Copy + Ord + AddAssign + CheckedSub + Encode + Decode;
+	/// The output of `Hashing`, used to identify blocks.
+	type Hash: Copy + Default + Eq + Debug + Encode + Decode;
+	/// The hash function used to hash block headers.
+	type Hashing: Hash<Output = Self::Hash>;
+
+	/// The origin of the calls dispatched by the runtime, see `RawOrigin`.
+	type RuntimeOrigin: From<RawOrigin<Self::AccountId>> + Into<RawOrigin<Self::AccountId>>;
+
+	/// The aggregated event type of the runtime, which includes the events of every pallet.
+	type RuntimeEvent: Clone + Debug + Encode + Decode;
+
+	/// The number of recent block hashes to keep around.
+	const BLOCK_HASH_COUNT: Self::BlockNumber;
+	/// The number of recent block receipts to keep around, see `Pallet::block_receipt`.
+	const RECEIPT_COUNT: Self::BlockNumber;
+	/// The maximum total weight of the calls in a block.
+	const MAX_BLOCK_WEIGHT: Weight;
+	/// The number of checkpoints to keep around, see `Pallet::checkpoint`.
+	const MAX_CHECKPOINTS: u32;
 }
 
 impl<T: Config> Pallet<T> {
-	pub fn new() -> Self {
-		Self { block_number: T::BlockNumber::zero(), nonce: BTreeMap::default() }
+	/// Create the system pallet on top of `storage`. If the storage does not hold a chain yet, it
+	/// starts at the genesis block 0, otherwise it carries on from the last imported block.
+	pub fn new(storage: &Storage) -> Self {
+		let mut pallet = Self {
+			storage: storage.clone(),
+			block_number: storage_item!(StorageValue<T::BlockNumber>, storage, "System", "BlockNumber"),
+			block_hash: storage_item!(StorageMap<T::BlockNumber, T::Hash>, storage, "System", "BlockHash"),
+			nonce: storage_item!(StorageMap<T::AccountId, T::Nonce>, storage, "System", "Nonce"),
+			events: TransactionalValue::new(Vec::new()),
+			phase: TransactionalValue::new(Phase::Initialization),
+			extrinsic_count: TransactionalValue::new(0),
+			block_weight: TransactionalValue::new(0),
+			author: TransactionalValue::new(None),
+		};
+		if pallet.block_number.get().is_none() {
+			let genesis = T::BlockNumber::zero();
+			pallet.block_number.set(genesis);
+			pallet
+				.block_hash
+				.insert(genesis, Self::genesis_header(genesis).hash::<T::Hashing>());
+		}
+		pallet
+	}
+
+	/// The header of the genesis block with the given number, which every chain builds on.
+	pub fn genesis_header(block_number: T::BlockNumber) -> Header<T::BlockNumber, T::Hash, T::AccountId> {
+		Header {
+			parent_hash: T::Hash::default(),
+			block_number,
+			state_root: T::Hash::default(),
+			extrinsics_root: T::Hash::default(),
+			author: None,
+		}
+	}
+
+	/// The storage shared by all the pallets of the runtime.
+	pub fn storage(&self) -> &Storage {
+		&self.storage
 	}
 
 	pub fn block_number(&self) -> T::BlockNumber {
-		self.block_number
+		self.block_number.get().unwrap_or_else(T::BlockNumber::zero)
 	}
 
 	pub fn inc_block_number(&mut self) {
-		self.block_number += T::BlockNumber::one();
+		let mut block_number = self.block_number();
+		block_number += T::BlockNumber::one();
+		self.block_number.set(block_number);
+	}
+
+	/// Start a new block authored by `author`: increment the block number, and reset the events of
+	/// the previous block.
+	pub fn initialize_block(&mut self, author: Option<T::AccountId>) {
+		self.inc_block_number();
+		self.reset_block(author);
+	}
+
+	/// Reset everything about the current block which is not part of the storage.
+	fn reset_block(&mut self, author: Option<T::AccountId>) {
+		self.author.set(author);
+		self.events.set(Vec::new());
+		self.phase.set(Phase::Initialization);
+		self.extrinsic_count.set(0);
+		self.block_weight.set(0);
+	}
+
+	/// The author of the current block, if any.
+	pub fn block_author(&self) -> Option<&T::AccountId> {
+		self.author.get().as_ref()
+	}
+
+	/// The weight consumed so far in the current block.
+	pub fn block_weight(&self) -> Weight {
+		*self.block_weight.get()
+	}
+
+	/// Consume `weight` from the current block, if it does not exceed `MAX_BLOCK_WEIGHT`.
+	pub fn consume_weight(&mut self, weight: Weight) -> Result<(), InvalidTransaction> {
+		let block_weight = self
+			.block_weight()
+			.checked_add(weight)
+			.ok_or(InvalidTransaction::ExhaustsResources)?;
+		if block_weight > T::MAX_BLOCK_WEIGHT {
+			return Err(InvalidTransaction::ExhaustsResources);
+		}
+		self.block_weight.set(block_weight);
+		Ok(())
+	}
+
+	/// Consume `weight` from the current block even if it exceeds `MAX_BLOCK_WEIGHT`, e.g. for the
+	/// weight of the hooks, which always run.
+	pub fn register_weight(&mut self, weight: Weight) {
+		self.block_weight.set(self.block_weight().saturating_add(weight));
+	}
+
+	/// Enter the phase of applying the next extrinsic of the block.
+	pub fn note_extrinsic(&mut self) {
+		let index = *self.extrinsic_count.get();
+		self.phase.set(Phase::ApplyExtrinsic(index));
+		self.extrinsic_count.set(index + 1);
+	}
+
+	/// Enter the phase after all extrinsics of the block have been applied.
+	pub fn note_finished_extrinsics(&mut self) {
+		self.phase.set(Phase::Finalization);
+	}
+
+	/// Record an event in the current phase of the block.
+	pub fn deposit_event(&mut self, event: T::RuntimeEvent) {
+		let phase = *self.phase.get();
+		self.events.get_mut().push(EventRecord { phase, event });
+	}
+
+	/// The events deposited so far in the current block.
+	pub fn events(&self) -> &[EventRecord<T::RuntimeEvent>] {
+		self.events.get()
+	}
+
+	/// Record a checkpoint of the state after the last imported block, which includes the storage of
+	/// every pallet. Only the last `MAX_CHECKPOINTS` checkpoints are kept, see `Storage::checkpoint`.
+	pub fn checkpoint(&mut self) {
+		self.storage
+			.checkpoint(self.block_number().encode(), T::MAX_CHECKPOINTS as usize);
+	}
+
+	/// The block numbers of the retained checkpoints, oldest first.
+	pub fn checkpoints(&self) -> Vec<T::BlockNumber> {
+		self.storage
+			.checkpoints()
+			.iter()
+			.map(|label| T::BlockNumber::decode_all(label).expect("checkpoints are labelled with their block number"))
+			.collect()
+	}
+
+	/// Revert the state to the retained checkpoint of block `number`, returning whether there was
+	/// one. The checkpoints of later blocks are dropped, and so is everything about the reverted
+	/// blocks which is not part of the storage, e.g. their receipts and the events and weight of the
+	/// last one.
+	pub fn revert_to(&mut self, number: T::BlockNumber) -> bool {
+		let last_number = self.block_number();
+		if !self.storage.revert_to(&number.encode()) {
+			return false;
+		}
+		let mut reverted = number;
+		while reverted < last_number {
+			reverted += T::BlockNumber::one();
+			self.forget_receipt(reverted);
+		}
+		self.reset_block(None);
+		true
+	}
+
+	/// The hash of block `number`, if it is recent enough to still be known.
+	pub fn block_hash(&self, number: T::BlockNumber) -> Option<T::Hash> {
+		self.block_hash.get(&number)
+	}
+
+	/// The hash of the last imported block, which the next block must use as its parent hash.
+	pub fn parent_hash(&self) -> T::Hash {
+		self.block_hash(self.block_number())
+			.expect("the hash of the last imported block is always kept")
+	}
+
+	/// Record the hash of the current block, and forget the hashes which are no longer recent.
+	pub fn note_block_hash(&mut self, hash: T::Hash) {
+		let block_number = self.block_number();
+		self.block_hash.insert(block_number, hash);
+		if let Some(expired) = block_number.checked_sub(&T::BLOCK_HASH_COUNT) {
+			self.block_hash.remove(expired);
+		}
+	}
+
+	/// Record the receipt of the current block, and forget the receipts which are no longer recent.
+	///
+	/// The receipts are not part of the state: they are kept in the auxiliary data of the storage,
+	/// see `Storage::insert_aux`, so they are written outside of any transaction. A block which is
+	/// reverted must have its receipt forgotten, see `forget_receipt`.
+	pub fn note_receipt(&mut self, receipt: BlockReceipt<T::RuntimeEvent>) {
+		let block_number = self.block_number();
+		self.storage.insert_aux(Self::receipt_key(block_number), receipt.encode());
+		if let Some(expired) = block_number.checked_sub(&T::RECEIPT_COUNT) {
+			self.forget_receipt(expired);
+		}
+	}
+
+	/// Forget the receipt of block `number`, e.g. because the block was reverted.
+	pub fn forget_receipt(&mut self, number: T::BlockNumber) {
+		self.storage.remove_aux(&Self::receipt_key(number));
+	}
+
+	/// The receipt of block `number`, if it is recent enough to still be known.
+	pub fn block_receipt(&self, number: T::BlockNumber) -> Option<BlockReceipt<T::RuntimeEvent>> {
+		let bytes = self.storage.get_aux(&Self::receipt_key(number))?;
+		Some(BlockReceipt::decode_all(&bytes).expect("receipts are stored encoded"))
+	}
+
+	/// The key of the receipt of block `number` in the auxiliary data of the storage.
+	fn receipt_key(number: T::BlockNumber) -> Vec<u8> {
+		[b"System:Receipts:".as_slice(), &number.encode()].concat()
+	}
+
+	/// The receipt of the extrinsic at `index` in block `number`, if the block is recent enough to
+	/// still be known.
+	pub fn extrinsic_receipt(&self, number: T::BlockNumber, index: usize) -> Option<ExtrinsicReceipt<T::RuntimeEvent>> {
+		self.block_receipt(number)?.extrinsics.into_iter().nth(index)
+	}
+
+	pub fn account_nonce(&self, who: &T::AccountId) -> T::Nonce {
+		self.nonce.get(who).unwrap_or_else(T::Nonce::zero)
 	}
 
 	pub fn inc_nonce(&mut self, who: &T::AccountId) {
-		let who_nonce = *self.nonce.get(who).unwrap_or(&T::Nonce::zero());
+		let who_nonce = self.account_nonce(who);
 
 		self.nonce.insert(who.clone(), who_nonce + T::Nonce::one());
 	}
+
+	/// Check that `nonce` is exactly the next nonce expected from `who`.
+	pub fn check_nonce(&self, who: &T::AccountId, nonce: T::Nonce) -> Result<(), InvalidTransaction> {
+		let expected = self.account_nonce(who);
+		if nonce < expected {
+			return Err(InvalidTransaction::Stale);
+		}
+		if nonce > expected {
+			return Err(InvalidTransaction::Future);
+		}
+		Ok(())
+	}
+}
+
+impl<T: Config> Transactional for Pallet<T> {
+	fn start_transaction(&mut self) {
+		self.storage.start_transaction();
+		self.events.start_transaction();
+		self.phase.start_transaction();
+		self.extrinsic_count.start_transaction();
+		self.block_weight.start_transaction();
+		self.author.start_transaction();
+	}
+
+	fn commit_transaction(&mut self) {
+		self.storage.commit_transaction();
+		self.events.commit_transaction();
+		self.phase.commit_transaction();
+		self.extrinsic_count.commit_transaction();
+		self.block_weight.commit_transaction();
+		self.author.commit_transaction();
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.storage.rollback_transaction();
+		self.events.rollback_transaction();
+		self.phase.rollback_transaction();
+		self.extrinsic_count.rollback_transaction();
+		self.block_weight.rollback_transaction();
+		self.author.rollback_transaction();
+	}
 }
 
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
 #[cfg(test)]
 mod test {
+	use super::{EventRecord, Phase};
+	use crate::support::{BlockReceipt, DispatchError, ExtrinsicReceipt, InvalidTransaction, ModuleError};
+
 	struct TestConfig;
 
 	impl super::Config for TestConfig {
 		type AccountId = String;
+		type Signature = crate::support::crypto::TestSignature<String>;
 		type Nonce = u32;
 		type BlockNumber = u32;
+		type Hash = crate::support::crypto::H256;
+		type Hashing = crate::support::crypto::Sha256;
+		type RuntimeOrigin = super::RawOrigin<String>;
+		type RuntimeEvent = String;
+
+		const BLOCK_HASH_COUNT: u32 = 2;
+		const RECEIPT_COUNT: u32 = 2;
+		const MAX_BLOCK_WEIGHT: u64 = 100;
+		const MAX_CHECKPOINTS: u32 = 2;
 	}
 
 	#[test]
 	fn init_system() {
-		let mut system = super::Pallet::<TestConfig>::new();
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
 		system.inc_block_number();
 		system.inc_nonce(&"alice".to_string());
 
-		assert_eq!(system.block_number, 1);
-		assert_eq!(system.nonce.get("alice"), Some(&1));
-		assert_eq!(system.nonce.get("bob"), None);
+		assert_eq!(system.block_number(), 1);
+		assert_eq!(system.nonce.get(&"alice".to_string()), Some(1));
+		assert_eq!(system.nonce.get(&"bob".to_string()), None);
+	}
+
+	#[test]
+	fn block_hashes() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+		let genesis_hash = system.parent_hash();
+		assert_eq!(system.block_hash(0), Some(genesis_hash));
+
+		for number in 1..=3u32 {
+			let header = crate::support::Header {
+				parent_hash: system.parent_hash(),
+				block_number: number,
+				state_root: Default::default(),
+				extrinsics_root: Default::default(),
+				author: None::<String>,
+			};
+			system.inc_block_number();
+			system.note_block_hash(header.hash::<crate::support::crypto::Sha256>());
+			assert_eq!(system.parent_hash(), header.hash::<crate::support::crypto::Sha256>());
+		}
+
+		// Only the last `BLOCK_HASH_COUNT` hashes are kept.
+		assert_eq!(system.block_hash(0), None);
+		assert_eq!(system.block_hash(1), None);
+		assert!(system.block_hash(2).is_some());
+		assert!(system.block_hash(3).is_some());
+	}
+
+	#[test]
+	fn checkpoints() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+		let alice = "alice".to_string();
+		let root = |system: &super::Pallet<TestConfig>| system.storage().root::<crate::support::crypto::Sha256>();
+		system.checkpoint();
+		let mut roots = vec![root(&system)];
+		for _ in 1..=3 {
+			system.inc_block_number();
+			system.inc_nonce(&alice);
+			system.checkpoint();
+			roots.push(root(&system));
+		}
+
+		// Only the last `MAX_CHECKPOINTS` checkpoints are kept.
+		assert_eq!(system.checkpoints(), vec![2, 3]);
+		assert!(!system.revert_to(1));
+
+		system.initialize_block(Some(alice.clone()));
+		system.note_extrinsic();
+		system.deposit_event("event".to_string());
+		system.register_weight(10);
+		assert!(system.revert_to(2));
+		// Nothing is left of the reverted block.
+		assert!(system.events().is_empty());
+		assert_eq!(system.block_weight(), 0);
+		assert_eq!(system.block_author(), None);
+		system.deposit_event("event".to_string());
+		assert_eq!(system.events()[0].phase, super::Phase::Initialization);
+		assert_eq!(system.block_number(), 2);
+		assert_eq!(system.account_nonce(&alice), 2);
+		assert_eq!(root(&system), roots[2]);
+		assert_eq!(system.checkpoints(), vec![2]);
+
+		// The checkpoint is kept, so the state can be reverted to it again.
+		system.inc_block_number();
+		system.inc_nonce(&alice);
+		assert!(system.revert_to(2));
+		assert_eq!(root(&system), roots[2]);
+	}
+
+	#[test]
+	fn receipts() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+		let receipt = |event: &str| BlockReceipt {
+			extrinsics: vec![ExtrinsicReceipt { result: Ok(Ok(())), events: vec![event.to_string()], weight: 10 }],
+		};
+		system.checkpoint();
+		for event in ["first", "second", "third"] {
+			system.inc_block_number();
+			system.note_receipt(receipt(event));
+			system.checkpoint();
+		}
+
+		// Only the receipts of the last `RECEIPT_COUNT` blocks are kept.
+		assert_eq!(system.block_receipt(1), None);
+		assert_eq!(system.block_receipt(3), Some(receipt("third")));
+		assert_eq!(system.extrinsic_receipt(2, 0).map(|receipt| receipt.events), Some(vec!["second".to_string()]));
+		assert_eq!(system.extrinsic_receipt(2, 1), None);
+
+		// The receipts are not part of the state, but reverting to block 2 still drops the receipt of
+		// block 3.
+		let root = system.storage().root::<crate::support::crypto::Sha256>();
+		assert!(system.revert_to(2));
+		assert_eq!(system.block_receipt(3), None);
+		assert_eq!(system.block_receipt(2), Some(receipt("second")));
+		system.inc_block_number();
+		system.note_receipt(receipt("third"));
+		assert_eq!(system.storage().root::<crate::support::crypto::Sha256>(), root);
+
+		// Errors are stored without their message, and `ModuleError`s are still equal without it.
+		let error =
+			DispatchError::Module(ModuleError { pallet_index: Some(1), error_index: 2, message: Some("Error") });
+		system.inc_block_number();
+		system.note_receipt(BlockReceipt {
+			extrinsics: vec![ExtrinsicReceipt { result: Ok(Err(error)), events: vec![], weight: 10 }],
+		});
+		let result = system.extrinsic_receipt(4, 0).map(|receipt| receipt.result);
+		assert_eq!(result, Some(Ok(Err(error))));
+		assert!(matches!(result, Some(Ok(Err(DispatchError::Module(ModuleError { message: None, .. }))))));
+	}
+
+	#[test]
+	fn genesis_config() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+		let genesis = super::GenesisConfig::<TestConfig> { block_number: 5 };
+		crate::support::BuildGenesisConfig::build(&genesis, &mut system);
+
+		assert_eq!(system.block_number(), 5);
+		assert_eq!(system.block_hash(0), None);
+		assert_eq!(
+			system.parent_hash(),
+			super::Pallet::<TestConfig>::genesis_header(5).hash::<crate::support::crypto::Sha256>()
+		);
+	}
+
+	#[test]
+	fn origins() {
+		use super::{ensure_root, ensure_signed, RawOrigin};
+		use crate::support::DispatchError;
+
+		let alice = "alice".to_string();
+		assert_eq!(ensure_signed::<TestConfig>(RawOrigin::Signed(alice.clone())), Ok(alice.clone()));
+		assert_eq!(ensure_signed::<TestConfig>(RawOrigin::Root), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_root::<TestConfig>(RawOrigin::Root), Ok(()));
+		assert_eq!(ensure_root::<TestConfig>(RawOrigin::Signed(alice)), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_root::<TestConfig>(RawOrigin::None), Err(DispatchError::BadOrigin));
+	}
+
+	#[test]
+	fn events() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+
+		system.initialize_block(None);
+		system.deposit_event("initialized".to_string());
+		system.note_extrinsic();
+		system.note_extrinsic();
+		system.deposit_event("applied".to_string());
+		system.note_finished_extrinsics();
+		system.deposit_event("finalized".to_string());
+
+		assert_eq!(
+			system.events(),
+			&[
+				EventRecord { phase: Phase::Initialization, event: "initialized".to_string() },
+				EventRecord { phase: Phase::ApplyExtrinsic(1), event: "applied".to_string() },
+				EventRecord { phase: Phase::Finalization, event: "finalized".to_string() },
+			]
+		);
+
+		// Events are reset at the start of every block.
+		system.initialize_block(None);
+		assert!(system.events().is_empty());
+	}
+
+	#[test]
+	fn block_weight() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+
+		system.initialize_block(None);
+		assert_eq!(system.consume_weight(60), Ok(()));
+		assert_eq!(system.consume_weight(50), Err(InvalidTransaction::ExhaustsResources));
+		assert_eq!(system.consume_weight(40), Ok(()));
+		assert_eq!(system.block_weight(), 100);
+
+		system.initialize_block(None);
+		assert_eq!(system.block_weight(), 0);
+
+		// Weight can be registered beyond the limit.
+		system.register_weight(60);
+		system.register_weight(60);
+		assert_eq!(system.block_weight(), 120);
+		assert_eq!(system.consume_weight(1), Err(InvalidTransaction::ExhaustsResources));
+	}
+
+	#[test]
+	fn check_nonce() {
+		let mut system = super::Pallet::<TestConfig>::new(&Default::default());
+		let alice = "alice".to_string();
+
+		assert_eq!(system.account_nonce(&alice), 0);
+		assert_eq!(system.check_nonce(&alice, 0), Ok(()));
+		assert_eq!(system.check_nonce(&alice, 1), Err(InvalidTransaction::Future));
+
+		system.inc_nonce(&alice);
+		assert_eq!(system.account_nonce(&alice), 1);
+		assert_eq!(system.check_nonce(&alice, 0), Err(InvalidTransaction::Stale));
+		assert_eq!(system.check_nonce(&alice, 1), Ok(()));
 	}
 }
@@ -0,0 +1,243 @@
+use num::{CheckedAdd, CheckedMul, FromPrimitive};
+
+use crate::{
+	balances,
+	support::{BuildGenesisConfig, Hooks, InvalidTransaction, Storage, Transactional, TransactionalValue, Weight},
+};
+
+pub trait Config: balances::Config<Balance: CheckedMul + FromPrimitive> + Sized {
+	/// The fee charged for every byte of an encoded extrinsic.
+	const TRANSACTION_BYTE_FEE: Self::Balance;
+	/// The fee charged for every unit of weight of a call.
+	const WEIGHT_FEE: Self::Balance;
+	/// Where the fees go once they have been withdrawn from the caller.
+	type FeeDestination: FeeDestination<Self>;
+}
+
+/// The initial state of the transaction payment pallet, which has no storage of its own.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(bound = "", deny_unknown_fields)]
+pub struct GenesisConfig<T: Config> {
+	#[serde(skip)]
+	_config: std::marker::PhantomData<T>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { _config: std::marker::PhantomData }
+	}
+}
+
+impl<T: Config> BuildGenesisConfig<Pallet<T>> for GenesisConfig<T> {
+	fn build(&self, _pallet: &mut Pallet<T>) {}
+}
+
+/// Decides what happens to the fee of an extrinsic once it has been paid.
+pub trait FeeDestination<T: Config> {
+	fn deposit_fee(balances: &mut balances::Pallet<T>, author: Option<&T::AccountId>, fee: T::Balance);
+}
+
+/// Burn all fees, removing them from circulation.
+pub struct Burn;
+
+impl<T: Config> FeeDestination<T> for Burn {
+	fn deposit_fee(_balances: &mut balances::Pallet<T>, _author: Option<&T::AccountId>, _fee: T::Balance) {}
+}
+
+/// Pay all fees to the author of the block. Fees are burnt if the block has no author, or if the
+/// author can't receive them.
+pub struct ToBlockAuthor;
+
+impl<T: Config> FeeDestination<T> for ToBlockAuthor {
+	fn deposit_fee(balances: &mut balances::Pallet<T>, author: Option<&T::AccountId>, fee: T::Balance) {
+		let deposited = author.is_some_and(|author| balances.deposit(author, fee).is_ok());
+		if !deposited {
+			Burn::deposit_fee(balances, author, fee);
+		}
+	}
+}
+
+pub struct Pallet<T: Config> {
+	/// Events deposited by this pallet, which are yet to be collected by the runtime.
+	events: TransactionalValue<Vec<Event<T>>>,
+}
+
+// `#[derive(Debug)]` would only require `T: Debug`, and not the types of the event fields.
+impl<T: Config> std::fmt::Debug for Pallet<T>
+where
+	Event<T>: std::fmt::Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("Pallet").field("events", &self.events).finish()
+	}
+}
+
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `who` paid `fee` for an extrinsic.
+	TransactionFeePaid { who: T::AccountId, fee: T::Balance },
+}
+
+/// This pallet has no errors of its own: an extrinsic whose fee can't be paid is invalid, see
+/// `InvalidTransaction::Payment`.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {}
+
+impl<T: Config> Pallet<T> {
+	/// This pallet does not store anything besides its events, so it does not use the `storage`.
+	pub fn new(_storage: &Storage) -> Self {
+		Self { events: TransactionalValue::new(Vec::new()) }
+	}
+
+	pub fn deposit_event(&mut self, event: Event<T>) {
+		self.events.get_mut().push(event);
+	}
+
+	/// Take all the events deposited so far, see `system::Pallet::deposit_event`.
+	pub fn take_events(&mut self) -> Vec<Event<T>> {
+		std::mem::take(self.events.get_mut())
+	}
+
+	/// The fee of an extrinsic which is `len` bytes long, and whose call has the given `weight`.
+	///
+	/// Returns `None` if the fee does not fit in a `Balance`.
+	pub fn compute_fee(len: u32, weight: Weight) -> Option<T::Balance> {
+		let length_fee = T::Balance::from_u32(len)?.checked_mul(&T::TRANSACTION_BYTE_FEE)?;
+		let weight_fee = T::Balance::from_u64(weight)?.checked_mul(&T::WEIGHT_FEE)?;
+		length_fee.checked_add(&weight_fee)
+	}
+
+	/// Withdraw the fee of an extrinsic from `who` before it is dispatched, returning the fee.
+	///
+	/// The extrinsic must be rejected if `who` can't pay the fee.
+	pub fn withdraw_fee(
+		&mut self,
+		balances: &mut balances::Pallet<T>,
+		who: &T::AccountId,
+		len: u32,
+		weight: Weight,
+	) -> Result<T::Balance, InvalidTransaction> {
+		let fee = Self::compute_fee(len, weight).ok_or(InvalidTransaction::Payment)?;
+		balances.withdraw(who, fee).map_err(|_| InvalidTransaction::Payment)?;
+		self.deposit_event(Event::TransactionFeePaid { who: who.clone(), fee });
+		Ok(fee)
+	}
+
+	/// Hand a fee which was withdrawn with `withdraw_fee` over to `T::FeeDestination`.
+	pub fn deposit_fee(&mut self, balances: &mut balances::Pallet<T>, author: Option<&T::AccountId>, fee: T::Balance) {
+		T::FeeDestination::deposit_fee(balances, author, fee);
+	}
+}
+
+impl<T: Config> Transactional for Pallet<T> {
+	fn start_transaction(&mut self) {
+		self.events.start_transaction();
+	}
+
+	fn commit_transaction(&mut self) {
+		self.events.commit_transaction();
+	}
+
+	fn rollback_transaction(&mut self) {
+		self.events.rollback_transaction();
+	}
+}
+
+impl<T: Config> Hooks<T::BlockNumber> for Pallet<T> {}
+
+/// This pallet has no callable functions, fees are charged by the runtime in `apply_extrinsic`.
+#[macros::call]
+impl<T: Config> Pallet<T> {}
+
+#[cfg(test)]
+mod tests {
+	use crate::{balances, support::InvalidTransaction};
+
+	/// Test configs which only differ by their fee destination.
+	macro_rules! test_config {
+		($config:ident, $fee_destination:ty) => {
+			struct $config;
+
+			impl super::Config for $config {
+				const TRANSACTION_BYTE_FEE: u32 = 1;
+				const WEIGHT_FEE: u32 = 2;
+				type FeeDestination = $fee_destination;
+			}
+
+			impl balances::Config for $config {
+				type Balance = u32;
+			}
+
+			impl crate::system::Config for $config {
+				type AccountId = String;
+				type Signature = crate::support::crypto::TestSignature<String>;
+				type Nonce = u32;
+				type BlockNumber = u32;
+				type Hash = crate::support::crypto::H256;
+				type Hashing = crate::support::crypto::Sha256;
+				type RuntimeOrigin = crate::system::RawOrigin<Self::AccountId>;
+				type RuntimeEvent = ();
+
+				const BLOCK_HASH_COUNT: u32 = 250;
+				const RECEIPT_COUNT: u32 = 250;
+				const MAX_BLOCK_WEIGHT: u64 = 1_000_000;
+				const MAX_CHECKPOINTS: u32 = 0;
+			}
+		};
+	}
+
+	test_config!(TestConfig, super::ToBlockAuthor);
+	test_config!(BurnConfig, super::Burn);
+
+	#[test]
+	fn withdraw_and_deposit_fee() {
+		let storage = crate::support::Storage::default();
+		let mut transaction_payment = super::Pallet::<TestConfig>::new(&storage);
+		let mut balances = balances::Pallet::<TestConfig>::new(&storage);
+		let alice = "alice".to_string();
+		let bob = "bob".to_string();
+		balances.set_balance(&alice, 100);
+
+		assert_eq!(super::Pallet::<TestConfig>::compute_fee(10, 20), Some(50));
+		assert_eq!(super::Pallet::<TestConfig>::compute_fee(0, u64::MAX), None);
+
+		assert_eq!(transaction_payment.withdraw_fee(&mut balances, &alice, 10, 20), Ok(50));
+		assert_eq!(balances.balance(&alice), 50);
+		assert_eq!(
+			transaction_payment.take_events(),
+			vec![super::Event::TransactionFeePaid { who: alice.clone(), fee: 50 }]
+		);
+
+		// Alice can't pay for a second extrinsic like this, and nothing is withdrawn.
+		assert_eq!(transaction_payment.withdraw_fee(&mut balances, &alice, 11, 20), Err(InvalidTransaction::Payment));
+		assert_eq!(balances.balance(&alice), 50);
+		assert!(transaction_payment.take_events().is_empty());
+
+		// The fee goes to the block author, or is burnt if there is none.
+		transaction_payment.deposit_fee(&mut balances, Some(&bob), 50);
+		assert_eq!(balances.balance(&bob), 50);
+		transaction_payment.deposit_fee(&mut balances, None, 50);
+		assert_eq!(balances.balance(&bob), 50);
+	}
+
+	#[test]
+	fn burn_fees() {
+		let storage = crate::support::Storage::default();
+		let mut transaction_payment = super::Pallet::<BurnConfig>::new(&storage);
+		let mut balances = balances::Pallet::<BurnConfig>::new(&storage);
+		let alice = "alice".to_string();
+		let bob = "bob".to_string();
+		balances.set_balance(&alice, 100);
+		let total_issuance =
+			|balances: &balances::Pallet<BurnConfig>| balances.balance(&alice) + balances.balance(&bob);
+
+		let fee = transaction_payment.withdraw_fee(&mut balances, &alice, 10, 20).unwrap();
+		transaction_payment.deposit_fee(&mut balances, Some(&bob), fee);
+
+		// Even with an author, the fee is gone from circulation.
+		assert_eq!(balances.balance(&alice), 50);
+		assert_eq!(balances.balance(&bob), 0);
+		assert_eq!(total_issuance(&balances), 50);
+	}
+}
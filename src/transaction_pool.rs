@@ -0,0 +1,291 @@
+use std::{
+	cmp::Reverse,
+	collections::{BTreeMap, BinaryHeap},
+	ops::Add,
+};
+
+use num::One;
+
+use crate::support::{Extrinsic, InvalidTransaction, TransactionPriority, TransactionValidity};
+
+/// The state which transactions are validated against, which is usually the `Runtime`.
+pub trait ValidateTransaction {
+	type AccountId: Ord + Clone;
+	type Nonce: Ord + Copy + One + Add<Output = Self::Nonce>;
+	type Call;
+	type Signature;
+
+	/// The nonce of the next transaction `who` can include in a block.
+	fn account_nonce(&self, who: &Self::AccountId) -> Self::Nonce;
+
+	/// Check whether `extrinsic` could be included in a block, possibly after other transactions of
+	/// the same signer. This must not change the state.
+	fn validate_transaction(&mut self, extrinsic: &ExtrinsicOf<Self>) -> TransactionValidity;
+}
+
+/// The extrinsics which are validated by `V`.
+pub type ExtrinsicOf<V> = Extrinsic<
+	<V as ValidateTransaction>::AccountId,
+	<V as ValidateTransaction>::Nonce,
+	<V as ValidateTransaction>::Call,
+	<V as ValidateTransaction>::Signature,
+>;
+
+/// The reasons a transaction can't be imported into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+	/// The transaction is not valid on top of the current state.
+	Invalid(InvalidTransaction),
+	/// The pool already has a transaction of the same signer with the same nonce.
+	AlreadyImported,
+}
+
+/// A transaction in the pool.
+///
+/// Transactions are ordered by priority, and then by the order in which they were imported.
+struct PooledTransaction<V: ValidateTransaction> {
+	extrinsic: ExtrinsicOf<V>,
+	priority: TransactionPriority,
+	/// The order in which transactions were imported, which breaks ties between priorities.
+	insertion: u64,
+}
+
+impl<V: ValidateTransaction> PartialEq for PooledTransaction<V> {
+	fn eq(&self, other: &Self) -> bool {
+		self.insertion == other.insertion
+	}
+}
+
+impl<V: ValidateTransaction> Eq for PooledTransaction<V> {}
+
+impl<V: ValidateTransaction> PartialOrd for PooledTransaction<V> {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<V: ValidateTransaction> Ord for PooledTransaction<V> {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		(self.priority, Reverse(self.insertion)).cmp(&(other.priority, Reverse(other.insertion)))
+	}
+}
+
+/// The transactions of each account, keyed by nonce.
+type Queue<V> =
+	BTreeMap<<V as ValidateTransaction>::AccountId, BTreeMap<<V as ValidateTransaction>::Nonce, PooledTransaction<V>>>;
+
+/// A pool of valid transactions waiting to be included in a block.
+///
+/// The transactions of an account are "ready" when their nonces follow the current nonce of the
+/// account without a gap, so they can be included in the next block in nonce order. All the other
+/// transactions are kept in the "future" queue, until the missing nonces are imported.
+pub struct TransactionPool<V: ValidateTransaction> {
+	ready: Queue<V>,
+	future: Queue<V>,
+	/// The insertion counter of the next imported transaction.
+	next_insertion: u64,
+}
+
+impl<V: ValidateTransaction> TransactionPool<V> {
+	pub fn new() -> Self {
+		Self { ready: BTreeMap::new(), future: BTreeMap::new(), next_insertion: 0 }
+	}
+
+	/// The number of transactions which can be included in the next block.
+	pub fn ready_len(&self) -> usize {
+		self.ready.values().map(BTreeMap::len).sum()
+	}
+
+	/// The number of transactions waiting for transactions with a lower nonce.
+	pub fn future_len(&self) -> usize {
+		self.future.values().map(BTreeMap::len).sum()
+	}
+
+	/// Validate `extrinsic` against `state`, and import it into the pool.
+	pub fn submit(&mut self, state: &mut V, extrinsic: ExtrinsicOf<V>) -> Result<(), PoolError> {
+		let insertion = self.next_insertion;
+		self.import(state, extrinsic, insertion)?;
+		self.next_insertion += 1;
+		Ok(())
+	}
+
+	/// The ready transactions, in the order they should be included in a block.
+	///
+	/// Transactions with a higher priority come first, but the transactions of an account always
+	/// come in nonce order. Transactions with the same priority come in the order they were imported.
+	pub fn ready(&self) -> Vec<&ExtrinsicOf<V>> {
+		let mut queues = self.ready.values().map(|queue| queue.values()).collect::<Vec<_>>();
+		// The next transaction of each account, with the index of its queue in `queues`.
+		let mut heads = BinaryHeap::new();
+		for (index, queue) in queues.iter_mut().enumerate() {
+			heads.extend(queue.next().map(|transaction| (transaction, index)));
+		}
+
+		let mut ready = Vec::new();
+		while let Some((transaction, index)) = heads.pop() {
+			ready.push(&transaction.extrinsic);
+			heads.extend(queues[index].next().map(|transaction| (transaction, index)));
+		}
+		ready
+	}
+
+	/// Validate every transaction in the pool against `state` again, e.g. after a block is imported.
+	///
+	/// Transactions which are no longer valid, including the ones which were just included in a
+	/// block, are evicted. Returns the number of evicted transactions.
+	pub fn maintain(&mut self, state: &mut V) -> usize {
+		let before = self.ready_len() + self.future_len();
+		let ready = std::mem::take(&mut self.ready);
+		let future = std::mem::take(&mut self.future);
+
+		// Transactions are imported in nonce order for each account, so that the ready ones are
+		// recognized as such.
+		let mut transactions = BTreeMap::new();
+		for (who, queue) in ready.into_iter().chain(future) {
+			transactions.entry(who).or_insert_with(BTreeMap::new).extend(queue);
+		}
+		for transaction in transactions.into_values().flat_map(BTreeMap::into_values) {
+			let _ = self.import(state, transaction.extrinsic, transaction.insertion);
+		}
+
+		before - self.ready_len() - self.future_len()
+	}
+
+	/// Validate `extrinsic`, and put it in the ready or future queue of its signer.
+	fn import(&mut self, state: &mut V, extrinsic: ExtrinsicOf<V>, insertion: u64) -> Result<(), PoolError> {
+		let valid = state.validate_transaction(&extrinsic).map_err(PoolError::Invalid)?;
+		let who = extrinsic.signer.clone();
+		let nonce = extrinsic.nonce;
+		let transaction = PooledTransaction { extrinsic, priority: valid.priority, insertion };
+
+		// The nonce which would extend the ready transactions of the signer.
+		let next_nonce = match self.ready.get(&who).and_then(|queue| queue.last_key_value()) {
+			Some((last_nonce, _)) => *last_nonce + V::Nonce::one(),
+			None => state.account_nonce(&who),
+		};
+		if nonce < next_nonce {
+			return Err(PoolError::AlreadyImported);
+		}
+		if nonce > next_nonce {
+			let queue = self.future.entry(who).or_default();
+			if queue.contains_key(&nonce) {
+				return Err(PoolError::AlreadyImported);
+			}
+			queue.insert(nonce, transaction);
+			return Ok(());
+		}
+
+		// The transaction is ready, and may fill the gap before some future transactions.
+		let ready = self.ready.entry(who.clone()).or_default();
+		ready.insert(nonce, transaction);
+		if let Some(future) = self.future.get_mut(&who) {
+			let mut next_nonce = nonce + V::Nonce::one();
+			while let Some(transaction) = future.remove(&next_nonce) {
+				ready.insert(next_nonce, transaction);
+				next_nonce = next_nonce + V::Nonce::one();
+			}
+			if future.is_empty() {
+				self.future.remove(&who);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::{PoolError, TransactionPool, ValidateTransaction};
+	use crate::support::{Extrinsic, InvalidTransaction, TransactionValidity, ValidTransaction};
+
+	/// Transactions are valid if their call, which is also their priority, is not zero.
+	struct TestState {
+		nonces: BTreeMap<&'static str, u32>,
+	}
+
+	impl ValidateTransaction for TestState {
+		type AccountId = &'static str;
+		type Nonce = u32;
+		type Call = u64;
+		type Signature = ();
+
+		fn account_nonce(&self, who: &&'static str) -> u32 {
+			self.nonces.get(who).copied().unwrap_or(0)
+		}
+
+		fn validate_transaction(&mut self, extrinsic: &Extrinsic<&'static str, u32, u64, ()>) -> TransactionValidity {
+			if extrinsic.nonce < self.account_nonce(&extrinsic.signer) {
+				return Err(InvalidTransaction::Stale);
+			}
+			if extrinsic.call == 0 {
+				return Err(InvalidTransaction::Payment);
+			}
+			Ok(ValidTransaction { priority: extrinsic.call })
+		}
+	}
+
+	fn extrinsic(signer: &'static str, nonce: u32, priority: u64) -> Extrinsic<&'static str, u32, u64, ()> {
+		Extrinsic { signer, signature: (), nonce, call: priority }
+	}
+
+	fn ready(pool: &TransactionPool<TestState>) -> Vec<(&'static str, u32)> {
+		pool.ready()
+			.into_iter()
+			.map(|extrinsic| (extrinsic.signer, extrinsic.nonce))
+			.collect()
+	}
+
+	#[test]
+	fn ready_and_future_queues() {
+		let mut state = TestState { nonces: BTreeMap::new() };
+		let mut pool = TransactionPool::new();
+
+		assert_eq!(pool.submit(&mut state, extrinsic("alice", 1, 1)), Ok(()));
+		assert_eq!(pool.submit(&mut state, extrinsic("alice", 2, 1)), Ok(()));
+		assert_eq!((pool.ready_len(), pool.future_len()), (0, 2));
+
+		// The missing nonce makes the future transactions ready.
+		assert_eq!(pool.submit(&mut state, extrinsic("alice", 0, 1)), Ok(()));
+		assert_eq!((pool.ready_len(), pool.future_len()), (3, 0));
+		assert_eq!(ready(&pool), vec![("alice", 0), ("alice", 1), ("alice", 2)]);
+
+		assert_eq!(pool.submit(&mut state, extrinsic("alice", 1, 1)), Err(PoolError::AlreadyImported));
+		assert_eq!(
+			pool.submit(&mut state, extrinsic("alice", 3, 0)),
+			Err(PoolError::Invalid(InvalidTransaction::Payment))
+		);
+	}
+
+	#[test]
+	fn priority_ordering() {
+		let mut state = TestState { nonces: BTreeMap::new() };
+		let mut pool = TransactionPool::new();
+
+		pool.submit(&mut state, extrinsic("alice", 0, 1)).unwrap();
+		pool.submit(&mut state, extrinsic("alice", 1, 10)).unwrap();
+		pool.submit(&mut state, extrinsic("bob", 0, 5)).unwrap();
+		pool.submit(&mut state, extrinsic("charlie", 0, 5)).unwrap();
+
+		// Bob and charlie go before alice, and bob was imported first. Alice's second transaction
+		// has the highest priority, but must wait for her first one.
+		assert_eq!(ready(&pool), vec![("bob", 0), ("charlie", 0), ("alice", 0), ("alice", 1)]);
+	}
+
+	#[test]
+	fn maintain_evicts_invalid_transactions() {
+		let mut state = TestState { nonces: BTreeMap::new() };
+		let mut pool = TransactionPool::new();
+
+		pool.submit(&mut state, extrinsic("alice", 0, 1)).unwrap();
+		pool.submit(&mut state, extrinsic("alice", 1, 1)).unwrap();
+		pool.submit(&mut state, extrinsic("bob", 2, 1)).unwrap();
+
+		// A block includes alice's first transaction, and bob's first two transactions.
+		state.nonces.insert("alice", 1);
+		state.nonces.insert("bob", 2);
+		assert_eq!(pool.maintain(&mut state), 1);
+		assert_eq!(ready(&pool), vec![("alice", 1), ("bob", 2)]);
+		assert_eq!(pool.future_len(), 0);
+	}
+}